@@ -0,0 +1,261 @@
+//! Account files, which hold a private key encrypted with a key derived from the user's password
+//!
+//! The current layout is:
+//! ```text
+//! "CFACCT" | version (1 byte) | memory KiB (u32) | iterations (u32) | parallelism (u32) | salt (16 bytes) | checksum (4 bytes) | key check (16 bytes) | nonce (24 bytes) | encrypted data
+//! ```
+//! with the numbers stored as little-endian. Everything before the nonce is also authenticated
+//! when decrypting so the cost can't be lowered by editing the file. Costs above
+//! `KdfParams::MAX` are refused before deriving the key.
+//!
+//! The checksum is the start of the SHA-256 of everything before it and the key check is the start
+//! of a SHA-256 of the derived key, so a damaged header, a wrong password and damaged encrypted
+//! data each give a different error.
+//!
+//! Files made before the header was added have no header and are encrypted with the SHA-256 of
+//! the password, these can still be read but should be rewritten with `write_account`.
+use crate::{
+	custom_types::{Error, Operation},
+	encrypt_decrypt::read_and_decrypt,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+	aead::{Aead, KeyInit, Payload},
+	XChaCha20Poly1305,
+};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fs;
+use zeroize::Zeroize;
+
+const MAGIC: &[u8] = b"CFACCT";
+pub const ACCOUNT_FILE_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const CHECKSUM_LENGTH: usize = 4;
+const KEY_CHECK_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+/// Up to the end of the salt, which is what the checksum covers
+const PARAMS_LENGTH: usize = MAGIC.len() + 1 + 4 * 3 + SALT_LENGTH;
+const HEADER_LENGTH: usize = PARAMS_LENGTH + CHECKSUM_LENGTH + KEY_CHECK_LENGTH;
+/// Hashed with the key to make the key check, so it can't be mistaken for a hash of anything else
+const KEY_CHECK_CONTEXT: &[u8] = b"crypto_forum account key check";
+
+/// The cost of deriving the key from the password, higher is slower to brute force
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KdfParams {
+	pub memory_kib: u32,
+	pub iterations: u32,
+	pub parallelism: u32,
+}
+
+impl KdfParams {
+	/// The most an account file can ask for, so a crafted file can't make reading it allocate
+	/// unbounded memory or run forever
+	pub const MAX: Self = Self {
+		memory_kib: 1024 * 1024,
+		iterations: 64,
+		parallelism: 64,
+	};
+
+	fn exceeds(&self, max: &Self) -> bool {
+		self.memory_kib > max.memory_kib
+			|| self.iterations > max.iterations
+			|| self.parallelism > max.parallelism
+	}
+}
+
+impl Default for KdfParams {
+	/// The minimum recommended by OWASP for Argon2id
+	fn default() -> Self {
+		Self {
+			memory_kib: 19 * 1024,
+			iterations: 2,
+			parallelism: 1,
+		}
+	}
+}
+
+/// The decrypted contents of an account file, which are zeroed when dropped
+pub struct AccountData {
+	pub data: Vec<u8>,
+	/// Whether the file is in an old format and should be rewritten with `write_account`
+	pub needs_migration: bool,
+}
+
+impl Drop for AccountData {
+	fn drop(&mut self) {
+		self.data.zeroize();
+	}
+}
+
+pub fn write_account(
+	file: &str,
+	data: &[u8],
+	password: &str,
+	params: &KdfParams,
+) -> Result<(), Error> {
+	encrypt_and_write(file, data, password, params)
+		.map_err(|e| e.context(Operation::WriteAccount, file))
+}
+
+fn encrypt_and_write(
+	file: &str,
+	data: &[u8],
+	password: &str,
+	params: &KdfParams,
+) -> Result<(), Error> {
+	let mut salt = [0; SALT_LENGTH];
+	OsRng.fill_bytes(&mut salt);
+	let mut nonce = [0; NONCE_LENGTH];
+	OsRng.fill_bytes(&mut nonce);
+
+	let mut key = derive_key(password, params, &salt)?;
+	let header = make_header(params, &salt, &key);
+	let cipher = XChaCha20Poly1305::new(&key.into());
+	key.zeroize();
+	let payload = Payload {
+		msg: data,
+		aad: &header,
+	};
+	let mut encrypted_data = cipher
+		.encrypt(&nonce.into(), payload)
+		.map_err(Error::Encryption)?;
+
+	let mut output = header;
+	output.extend_from_slice(&nonce);
+	output.append(&mut encrypted_data);
+
+	// Write to a temporary file first so an existing account isn't lost if writing fails
+	let temp_file = format!("{file}.tmp");
+	fs::write(&temp_file, output).map_err(Error::StdIo)?;
+	fs::rename(&temp_file, file).map_err(Error::StdIo)
+}
+
+pub fn read_account(file: &str, password: &str) -> Result<AccountData, Error> {
+	read_and_decrypt_account(file, password).map_err(|e| e.context(Operation::ReadAccount, file))
+}
+
+fn read_and_decrypt_account(file: &str, password: &str) -> Result<AccountData, Error> {
+	let file_data = fs::read(file).map_err(Error::StdIo)?;
+	if !file_data.starts_with(MAGIC) {
+		let legacy_key: [u8; 32] = Sha256::digest(password).into();
+		let data = match read_and_decrypt(file, &legacy_key) {
+			Ok(data) => data,
+			// Without a key check a wrong key can't be told apart from damaged data, but a wrong
+			// password is far more likely
+			Err(Error::Encryption(_)) => return Err(Error::WrongPassword),
+			Err(e) => return Err(e),
+		};
+		return Ok(AccountData {
+			data,
+			needs_migration: true,
+		});
+	}
+
+	if file_data.len() <= HEADER_LENGTH + NONCE_LENGTH {
+		return Err(Error::InvalidFileData(format!("{file} is truncated")));
+	}
+	let (header, rest) = file_data.split_at(HEADER_LENGTH);
+	let (params, salt) = parse_header(file, header)?;
+	let (nonce, encrypted_data) = rest.split_at(NONCE_LENGTH);
+
+	let mut key = derive_key(password, &params, &salt)?;
+	if header[PARAMS_LENGTH + CHECKSUM_LENGTH..] != key_check(&key) {
+		key.zeroize();
+		return Err(Error::WrongPassword);
+	}
+	let cipher = XChaCha20Poly1305::new(&key.into());
+	key.zeroize();
+	let payload = Payload {
+		msg: encrypted_data,
+		aad: header,
+	};
+	// The key is right, so failing to decrypt means the encrypted data is damaged
+	let data = cipher
+		.decrypt(nonce.into(), payload)
+		.map_err(|_| Error::InvalidFileData(format!("{file} has damaged encrypted data")))?;
+	Ok(AccountData {
+		data,
+		needs_migration: false,
+	})
+}
+
+fn make_header(params: &KdfParams, salt: &[u8; SALT_LENGTH], key: &[u8; 32]) -> Vec<u8> {
+	let mut header = MAGIC.to_vec();
+	header.push(ACCOUNT_FILE_VERSION);
+	header.extend_from_slice(&params.memory_kib.to_le_bytes());
+	header.extend_from_slice(&params.iterations.to_le_bytes());
+	header.extend_from_slice(&params.parallelism.to_le_bytes());
+	header.extend_from_slice(salt);
+	header.extend_from_slice(&checksum(&header));
+	header.extend_from_slice(&key_check(key));
+	header
+}
+
+fn parse_header(file: &str, header: &[u8]) -> Result<(KdfParams, [u8; SALT_LENGTH]), Error> {
+	let stored_checksum = &header[PARAMS_LENGTH..PARAMS_LENGTH + CHECKSUM_LENGTH];
+	if stored_checksum != checksum(&header[..PARAMS_LENGTH]) {
+		return Err(Error::InvalidFileData(format!(
+			"{file} has a damaged header"
+		)));
+	}
+	let version = header[MAGIC.len()];
+	if version != ACCOUNT_FILE_VERSION {
+		let message = format!("{file} has unsupported account file version {version}");
+		return Err(Error::InvalidFileData(message));
+	}
+	let numbers = &header[MAGIC.len() + 1..];
+	let read_u32 = |index: usize| {
+		let bytes = &numbers[index * 4..index * 4 + 4];
+		u32::from_le_bytes(bytes.try_into().expect("Slice is always 4 bytes"))
+	};
+	let params = KdfParams {
+		memory_kib: read_u32(0),
+		iterations: read_u32(1),
+		parallelism: read_u32(2),
+	};
+	if params.exceeds(&KdfParams::MAX) {
+		let message =
+			format!("{file} asks for a key derivation cost above the maximum: {params:?}");
+		return Err(Error::InvalidFileData(message));
+	}
+	let salt = header[PARAMS_LENGTH - SALT_LENGTH..PARAMS_LENGTH]
+		.try_into()
+		.expect("Slice is always the length of the salt");
+	Ok((params, salt))
+}
+
+fn derive_key(password: &str, params: &KdfParams, salt: &[u8]) -> Result<[u8; 32], Error> {
+	let argon2_params = Params::new(
+		params.memory_kib,
+		params.iterations,
+		params.parallelism,
+		Some(32),
+	)
+	.map_err(|e| Error::KeyDerivation(e.to_string()))?;
+	let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params);
+
+	let mut key = [0; 32];
+	argon2
+		.hash_password_into(password.as_bytes(), salt, &mut key)
+		.map_err(|e| Error::KeyDerivation(e.to_string()))?;
+	Ok(key)
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+	let hash = Sha256::digest(data);
+	hash[..CHECKSUM_LENGTH]
+		.try_into()
+		.expect("Slice is always the length of the checksum")
+}
+
+/// Shows whether the key is right without decrypting, the key can't be worked out from it
+fn key_check(key: &[u8; 32]) -> [u8; KEY_CHECK_LENGTH] {
+	let hash = Sha256::new()
+		.chain_update(KEY_CHECK_CONTEXT)
+		.chain_update(key)
+		.finalize();
+	hash[..KEY_CHECK_LENGTH]
+		.try_into()
+		.expect("Slice is always the length of the key check")
+}
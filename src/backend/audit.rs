@@ -0,0 +1,106 @@
+//! Checking that a whole thread is intact: every message decodes, is properly signed, and replies
+//! to a message that came before it
+use crate::{chains::ROOT_HASH, custom_types::*};
+use std::{collections::HashSet, fmt};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LinkProblem {
+	/// The message it replies to comes after it in the file
+	ParentAfter,
+	/// The message it replies to isn't in the file at all
+	ParentMissing,
+}
+
+impl fmt::Display for LinkProblem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::ParentAfter => write!(f, "Replies to a message that comes after it"),
+			Self::ParentMissing => write!(f, "Replies to a message that is not in the file"),
+		}
+	}
+}
+
+/// The result of checking a single message, `index` is its position in the file
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuditEntry {
+	pub index: usize,
+	pub status: MessageStatus,
+	/// `None` if the message is linked properly or couldn't be decoded
+	pub link_problem: Option<LinkProblem>,
+}
+
+impl AuditEntry {
+	pub fn is_ok(&self) -> bool {
+		self.status == MessageStatus::Valid && self.link_problem.is_none()
+	}
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuditReport {
+	pub entries: Vec<AuditEntry>,
+	/// How many messages have more than one reply
+	pub forks: usize,
+}
+
+impl AuditReport {
+	/// Whether every message is valid and properly linked
+	pub fn is_intact(&self) -> bool {
+		self.entries.iter().all(AuditEntry::is_ok)
+	}
+
+	pub fn failures(&self) -> impl Iterator<Item = &AuditEntry> {
+		self.entries.iter().filter(|entry| !entry.is_ok())
+	}
+}
+
+pub fn audit_messages(verified_messages: &[VerifiedMessage]) -> AuditReport {
+	let all_hashes: HashSet<[u8; 64]> = verified_messages
+		.iter()
+		.filter_map(|verified| verified.message.as_ref())
+		.map(Message::get_hash)
+		.collect();
+
+	let mut earlier_hashes = HashSet::<[u8; 64]>::new();
+	let mut replied_to = HashSet::<[u8; 64]>::new();
+	let mut forks = HashSet::<[u8; 64]>::new();
+	let mut entries = Vec::<AuditEntry>::new();
+	for (index, verified) in verified_messages.iter().enumerate() {
+		let link_problem = match &verified.message {
+			Some(message) => {
+				let hash = message.get_hash();
+				// A message that's in the file twice isn't a fork
+				if !earlier_hashes.contains(&hash) && !replied_to.insert(message.prev_hash) {
+					forks.insert(message.prev_hash);
+				}
+				let problem = get_link_problem(message, &earlier_hashes, &all_hashes);
+				earlier_hashes.insert(hash);
+				problem
+			}
+			None => None,
+		};
+		entries.push(AuditEntry {
+			index,
+			status: verified.status,
+			link_problem,
+		});
+	}
+	AuditReport {
+		entries,
+		forks: forks.len(),
+	}
+}
+
+fn get_link_problem(
+	message: &Message,
+	earlier_hashes: &HashSet<[u8; 64]>,
+	all_hashes: &HashSet<[u8; 64]>,
+) -> Option<LinkProblem> {
+	let parent = &message.prev_hash;
+	if *parent == ROOT_HASH || earlier_hashes.contains(parent) {
+		None
+	} else if all_hashes.contains(parent) {
+		Some(LinkProblem::ParentAfter)
+	} else {
+		Some(LinkProblem::ParentMissing)
+	}
+}
@@ -0,0 +1,228 @@
+//! Blocking versions of the `read` and `write` functions for code that isn't async
+//!
+//! These all share one runtime, so they must not be called from inside an async context
+//! (use the async versions there instead).
+use crate::{
+	custom_types::*,
+	encoding::FileFormat,
+	forum::{self, ForumThread},
+	linked::MessageCache,
+	mirrors::{self, MirroredThread},
+	read,
+	store::ForumStore,
+	write,
+};
+use ed25519_dalek::Keypair;
+use std::{future::Future, sync::OnceLock};
+use tokio::runtime::Runtime;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+pub fn get_messages(store: &impl ForumStore, link: &str) -> Result<Vec<Message>, Error> {
+	block_on(read::get_messages(store, link))?
+}
+
+pub fn get_verified_messages(
+	store: &impl ForumStore,
+	link: &str,
+) -> Result<Vec<VerifiedMessage>, Error> {
+	block_on(read::get_verified_messages(store, link))?
+}
+
+pub fn get_repo(store: &impl ForumStore, link: &str) -> Result<FullFile, Error> {
+	block_on(read::get_repo(store, link))?
+}
+
+/// The hash of the last message of each chain, only fetching the newest of each if they can be
+pub fn get_chain_ends(
+	store: &impl ForumStore,
+	link: &str,
+	cache: &mut MessageCache,
+) -> Result<Vec<[u8; 64]>, Error> {
+	block_on(read::get_chain_ends(store, link, cache))?
+}
+
+/// Get only the thread's header, messages stored as their own objects aren't fetched
+pub fn get_header(store: &impl ForumStore, link: &str) -> Result<Header, Error> {
+	block_on(read::get_header(store, link))?
+}
+
+/// Like `get_repo`, but messages already in the cache aren't fetched again
+pub fn get_repo_cached(
+	store: &impl ForumStore,
+	link: &str,
+	cache: &mut MessageCache,
+) -> Result<FullFile, Error> {
+	block_on(read::get_repo_cached(store, link, cache))?
+}
+
+pub fn read_file(store: &impl ForumStore, ipns_link: &str) -> Result<String, Error> {
+	block_on(read::read_file(store, ipns_link))?
+}
+
+/// Get the file as it's stored, which isn't text if it's in the binary format
+pub fn read_raw_file(store: &impl ForumStore, ipns_link: &str) -> Result<Vec<u8>, Error> {
+	block_on(read::read_raw_file(store, ipns_link))?
+}
+
+/// Get the file as it's stored, with messages stored as their own objects put back in it
+pub fn read_standalone_file(store: &impl ForumStore, link: &str) -> Result<Vec<u8>, Error> {
+	block_on(read::read_standalone_file(store, link))?
+}
+
+/// Read the thread and every mirror listed in its header, merging messages with the same hash
+pub fn get_mirrored_messages(store: &impl ForumStore, link: &str) -> Result<MirroredThread, Error> {
+	block_on(mirrors::get_mirrored_messages(store, link))?
+}
+
+/// Append the messages to the GitArk repo at the link
+pub fn write_messages(
+	store: &impl ForumStore,
+	link: &str,
+	messages: Vec<Message>,
+) -> Result<(), Error> {
+	block_on(write::write_messages(store, link, messages))?
+}
+
+/// Like `write_messages`, but messages already in the cache aren't fetched again
+pub fn write_messages_cached(
+	store: &impl ForumStore,
+	link: &str,
+	messages: Vec<Message>,
+	cache: &mut MessageCache,
+) -> Result<(), Error> {
+	block_on(write::write_messages_cached(store, link, messages, cache))?
+}
+
+/// Add a revision to the header, signed by the owner or anyone if the thread has no owner yet
+pub fn edit_header(
+	store: &impl ForumStore,
+	link: &str,
+	revision: HeaderRevision,
+) -> Result<(), Error> {
+	block_on(write::edit_header(store, link, revision))?
+}
+
+/// Add a contributor's copy of the thread to the header, so readers also fetch messages from it
+pub fn add_mirror(
+	store: &impl ForumStore,
+	link: &str,
+	mirror: &str,
+	keypair: &Keypair,
+) -> Result<(), Error> {
+	block_on(write::add_mirror(store, link, mirror, keypair))?
+}
+
+/// Append a new version of the member list to the header
+pub fn add_member_list(
+	store: &impl ForumStore,
+	link: &str,
+	member_list: MemberList,
+) -> Result<(), Error> {
+	block_on(write::add_member_list(store, link, member_list))?
+}
+
+/// Republish the thread in another format, nothing happens if it's already in that format
+pub fn convert_repo(store: &impl ForumStore, link: &str, format: FileFormat) -> Result<(), Error> {
+	block_on(write::convert_repo(store, link, format))?
+}
+
+/// Move the thread's messages into their own objects, so posting only uploads the new message
+pub fn link_messages(store: &impl ForumStore, link: &str) -> Result<(), Error> {
+	block_on(write::link_messages(store, link))?
+}
+
+/// Republish the thread in the newest format version, returns the version it was in before
+pub fn migrate_repo(store: &impl ForumStore, link: &str) -> Result<u32, Error> {
+	block_on(write::migrate_repo(store, link))?
+}
+
+/// Creates a new IPNS link with the header and no messages, returns the link to it
+pub fn new_ipns(store: &impl ForumStore, header: Header) -> Result<String, Error> {
+	block_on(write::new_ipns(store, header))?
+}
+
+/// Creates a new IPNS link with the header signed by the keypair, which makes them the owner
+pub fn new_owned_ipns(
+	store: &impl ForumStore,
+	header: Header,
+	keypair: &Keypair,
+) -> Result<String, Error> {
+	block_on(write::new_owned_ipns(store, header, keypair))?
+}
+
+/// Publishes the manifest to a new IPNS link signed by the keypair, returns the link to it
+pub fn new_forum(
+	store: &impl ForumStore,
+	manifest: &ForumManifest,
+	keypair: &Keypair,
+) -> Result<String, Error> {
+	block_on(forum::new_forum(store, manifest, keypair))?
+}
+
+/// Read the manifest, which fails if it isn't signed by the owner it lists, or by `owner` if given
+pub fn get_manifest(
+	store: &impl ForumStore,
+	link: &str,
+	owner: Option<&str>,
+) -> Result<ForumManifest, Error> {
+	block_on(forum::get_manifest(store, link, owner))?
+}
+
+/// Read a copy of the forum's manifest from a mirror, which must be signed by the forum's owner
+pub fn get_mirrored_manifest(
+	store: &impl ForumStore,
+	forum_link: &str,
+	mirror: &str,
+	owner: &str,
+) -> Result<ForumManifest, Error> {
+	block_on(forum::get_mirrored_manifest(
+		store, forum_link, mirror, owner,
+	))?
+}
+
+/// Replace the manifest with a new version, which must be signed by the current owner
+pub fn write_manifest(
+	store: &impl ForumStore,
+	link: &str,
+	manifest: &ForumManifest,
+) -> Result<(), Error> {
+	block_on(forum::write_manifest(store, link, manifest))?
+}
+
+/// Add the thread to the end of the forum, nothing happens if it is already there
+pub fn add_thread(
+	store: &impl ForumStore,
+	forum_link: &str,
+	thread_link: &str,
+	keypair: &Keypair,
+) -> Result<(), Error> {
+	block_on(forum::add_thread(store, forum_link, thread_link, keypair))?
+}
+
+/// Read the header of every thread in the forum, ordered by thread number
+pub fn list_threads(
+	store: &impl ForumStore,
+	link: &str,
+	owner: Option<&str>,
+) -> Result<Vec<ForumThread>, Error> {
+	block_on(forum::list_threads(store, link, owner))?
+}
+
+pub fn delete_key(store: &impl ForumStore, key: &str) -> Result<(), Error> {
+	block_on(write::delete_key(store, key))?
+}
+
+fn block_on<F: Future>(future: F) -> Result<F::Output, Error> {
+	let runtime = match RUNTIME.get() {
+		Some(runtime) => runtime,
+		None => {
+			let new_runtime = tokio::runtime::Builder::new_current_thread()
+				.enable_all()
+				.build()
+				.map_err(Error::StdIo)?;
+			RUNTIME.get_or_init(|| new_runtime)
+		}
+	};
+	Ok(runtime.block_on(future))
+}
@@ -0,0 +1,145 @@
+//! Working out the structure of a thread from the `prev_hash` of each message
+//!
+//! If two people reply to the same message before seeing each other's reply the thread splits
+//! into multiple chains, so the last message in the file is not necessarily the one to reply to.
+use crate::custom_types::Message;
+use std::collections::{HashMap, HashSet};
+
+/// The `prev_hash` of the first message in a thread
+pub const ROOT_HASH: [u8; 64] = [0; 64];
+
+/// A message that has more than one reply
+#[derive(Debug, PartialEq)]
+pub struct Fork<'a> {
+	/// The hash of the message being replied to (`ROOT_HASH` if there are multiple first messages)
+	pub parent: [u8; 64],
+	pub replies: Vec<&'a Message>,
+}
+
+/// The messages of a thread linked together by their hashes
+pub struct MessageGraph<'a> {
+	/// Every message with a unique hash, in the order they appear in the file
+	messages: Vec<(&'a Message, [u8; 64])>,
+	hashes: HashSet<[u8; 64]>,
+	/// The hash of a message to the messages replying to it
+	replies: HashMap<[u8; 64], Vec<&'a Message>>,
+}
+
+impl<'a> MessageGraph<'a> {
+	pub fn new(messages: &'a [Message]) -> Self {
+		let mut unique = Vec::<(&Message, [u8; 64])>::new();
+		let mut hashes = HashSet::<[u8; 64]>::new();
+		for message in messages {
+			let hash = message.get_hash();
+			// The same message can be in the file twice if it was written from multiple copies
+			if hashes.insert(hash) {
+				unique.push((message, hash));
+			}
+		}
+
+		let mut replies = HashMap::<[u8; 64], Vec<&Message>>::new();
+		for (message, _) in &unique {
+			replies.entry(message.prev_hash).or_default().push(message);
+		}
+		Self {
+			messages: unique,
+			hashes,
+			replies,
+		}
+	}
+
+	/// Messages that don't reply to any other message in the thread
+	///
+	/// This is the first message(s) of the thread as well as any whose parent is missing
+	pub fn get_roots(&self) -> Vec<&'a Message> {
+		self.messages
+			.iter()
+			.filter(|(message, _)| !self.hashes.contains(&message.prev_hash))
+			.map(|(message, _)| *message)
+			.collect()
+	}
+
+	pub fn get_replies(&self, hash: &[u8; 64]) -> Vec<&'a Message> {
+		self.replies.get(hash).cloned().unwrap_or_default()
+	}
+
+	/// How many messages are in the subthread starting at the message (including the message)
+	pub fn count_subthread(&self, message: &Message) -> usize {
+		let mut count = 0;
+		let mut to_count = vec![message.get_hash()];
+		while let Some(hash) = to_count.pop() {
+			count += 1;
+			let replies = self.get_replies(&hash);
+			to_count.extend(replies.iter().map(|reply| reply.get_hash()));
+		}
+		count
+	}
+
+	/// Every point where the thread splits into multiple chains
+	pub fn get_forks(&self) -> Vec<Fork<'a>> {
+		let mut forks = Vec::<Fork>::new();
+		let mut seen = HashSet::<[u8; 64]>::new();
+		// Go through in file order so the forks are always returned in the same order
+		for (message, _) in &self.messages {
+			let parent = message.prev_hash;
+			if !seen.insert(parent) {
+				continue;
+			}
+			let replies = self.get_replies(&parent);
+			if replies.len() > 1 {
+				forks.push(Fork { parent, replies });
+			}
+		}
+		forks
+	}
+
+	/// Every chain of validly signed messages, from the first message to the last
+	///
+	/// A chain stops at a message with an invalid signature as we cannot prove who wrote it,
+	/// so nothing after it can be trusted to be part of the chain.
+	pub fn get_chains(&self) -> Vec<Vec<&'a Message>> {
+		let mut chains = Vec::<Vec<&Message>>::new();
+		// Use a stack instead of recursion so a long thread can't overflow the call stack
+		let mut unfinished: Vec<Vec<&Message>> = self
+			.get_roots()
+			.into_iter()
+			.rev()
+			.filter(|root| root.is_signed())
+			.map(|root| vec![root])
+			.collect();
+		while let Some(mut chain) = unfinished.pop() {
+			let last = chain
+				.last()
+				.expect("Chains always have at least one message");
+			let valid_replies = self.get_valid_replies(last);
+			let Some((first_reply, other_replies)) = valid_replies.split_first() else {
+				chains.push(chain);
+				continue;
+			};
+			// Push in reverse so the replies are popped in file order
+			for reply in other_replies.iter().rev() {
+				let mut new_chain = chain.clone();
+				new_chain.push(reply);
+				unfinished.push(new_chain);
+			}
+			chain.push(first_reply);
+			unfinished.push(chain);
+		}
+		chains
+	}
+
+	fn get_valid_replies(&self, message: &Message) -> Vec<&'a Message> {
+		self.get_replies(&message.get_hash())
+			.into_iter()
+			.filter(|reply| reply.is_signed())
+			.collect()
+	}
+}
+
+pub fn get_chains(messages: &[Message]) -> Vec<Vec<&Message>> {
+	MessageGraph::new(messages).get_chains()
+}
+
+pub fn get_forks(messages: &[Message]) -> Vec<Fork<'_>> {
+	MessageGraph::new(messages).get_forks()
+}
@@ -1,31 +1,188 @@
 use base64::{engine::general_purpose, Engine};
-use ed25519_dalek::Verifier;
+use ed25519_dalek::{Signer, Verifier};
 use sha2::{Digest, Sha512};
-use std::{fmt, fs};
+use std::{fmt, fs, time::Duration};
 
 #[derive(Debug)]
 pub enum Error {
 	StdIo(std::io::Error),
 	Encryption(chacha20poly1305::aead::Error),
+	KeyDerivation(String),
+	WrongPassword,
 	TomlSerialization(toml::ser::Error),
 	TomlDeserialization(toml::de::Error),
+	MsgpackSerialization(rmp_serde::encode::Error),
+	MsgpackDeserialization(rmp_serde::decode::Error),
 	InvalidFileData(String),
 	SignatureError(ed25519_dalek::SignatureError),
 	IPFS(ipfs_api_backend_hyper::Error),
 	FromUtf8(std::string::FromUtf8Error),
+	NotFound(String),
+	AlreadyExists(String),
+	InvalidConfig(String),
+	/// Signed by a key that isn't allowed to make the change
+	Unauthorized(String),
+	Timeout(Duration),
+	/// An error along with what was being done and to which link or file
+	Context {
+		operation: Operation,
+		target: String,
+		source: Box<Error>,
+	},
+}
+
+impl Error {
+	/// Wrap the error with what was being done when it happened
+	pub fn context(self, operation: Operation, target: impl Into<String>) -> Self {
+		Self::Context {
+			operation,
+			target: target.into(),
+			source: Box::new(self),
+		}
+	}
+
+	/// The error without any context around it, for matching on what went wrong
+	pub fn root(&self) -> &Self {
+		match self {
+			Self::Context { source, .. } => source.root(),
+			error => error,
+		}
+	}
 }
 
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
-			Self::StdIo(string) => write!(f, "std::io error:{string}"),
+			Self::StdIo(_) => write!(f, "std::io error"),
 			Self::Encryption(string) => write!(f, "Encryption error:{string}"),
-			Self::TomlSerialization(string) => write!(f, "TOML serialization error:{string}"),
-			Self::TomlDeserialization(string) => write!(f, "TOML serialization error:{string}"),
+			Self::KeyDerivation(string) => write!(f, "Key derivation error:{string}"),
+			Self::WrongPassword => write!(f, "Wrong password"),
+			Self::TomlSerialization(_) => write!(f, "TOML serialization error"),
+			Self::TomlDeserialization(_) => write!(f, "TOML deserialization error"),
+			Self::MsgpackSerialization(_) => write!(f, "MessagePack serialization error"),
+			Self::MsgpackDeserialization(_) => write!(f, "MessagePack deserialization error"),
 			Self::InvalidFileData(string) => write!(f, "Invalid file data error:{string}"),
-			Self::SignatureError(string) => write!(f, "Signature error:{string}"),
-			Self::IPFS(string) => write!(f, "IPFS error:{string}"),
-			Self::FromUtf8(string) => write!(f, "Error converting from UTF-8:{string}"),
+			Self::SignatureError(_) => write!(f, "Signature error"),
+			Self::IPFS(_) => write!(f, "IPFS error"),
+			Self::FromUtf8(_) => write!(f, "Error converting from UTF-8"),
+			Self::NotFound(string) => write!(f, "Not found error:{string}"),
+			Self::AlreadyExists(string) => write!(f, "Already exists error:{string}"),
+			Self::InvalidConfig(string) => write!(f, "Invalid config error:{string}"),
+			Self::Unauthorized(string) => write!(f, "Unauthorized error:{string}"),
+			Self::Timeout(duration) => write!(f, "Timed out after {}s", duration.as_secs_f32()),
+			Self::Context {
+				operation, target, ..
+			} => write!(f, "Error {operation} {target}"),
+		}
+	}
+}
+
+/// Wrapped errors are returned from `source` rather than included in `Display`, so walk the chain to
+/// get the full message. Use `root` to match on the underlying error.
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::StdIo(e) => Some(e),
+			Self::TomlSerialization(e) => Some(e),
+			Self::TomlDeserialization(e) => Some(e),
+			Self::MsgpackSerialization(e) => Some(e),
+			Self::MsgpackDeserialization(e) => Some(e),
+			Self::SignatureError(e) => Some(e),
+			Self::IPFS(e) => Some(e),
+			Self::FromUtf8(e) => Some(e),
+			Self::Context { source, .. } => Some(source.as_ref()),
+			// `aead::Error` only implements `std::error::Error` with its std feature
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(error: std::io::Error) -> Self {
+		Self::StdIo(error)
+	}
+}
+
+impl From<chacha20poly1305::aead::Error> for Error {
+	fn from(error: chacha20poly1305::aead::Error) -> Self {
+		Self::Encryption(error)
+	}
+}
+
+impl From<toml::ser::Error> for Error {
+	fn from(error: toml::ser::Error) -> Self {
+		Self::TomlSerialization(error)
+	}
+}
+
+impl From<toml::de::Error> for Error {
+	fn from(error: toml::de::Error) -> Self {
+		Self::TomlDeserialization(error)
+	}
+}
+
+impl From<rmp_serde::encode::Error> for Error {
+	fn from(error: rmp_serde::encode::Error) -> Self {
+		Self::MsgpackSerialization(error)
+	}
+}
+
+impl From<rmp_serde::decode::Error> for Error {
+	fn from(error: rmp_serde::decode::Error) -> Self {
+		Self::MsgpackDeserialization(error)
+	}
+}
+
+impl From<ed25519_dalek::SignatureError> for Error {
+	fn from(error: ed25519_dalek::SignatureError) -> Self {
+		Self::SignatureError(error)
+	}
+}
+
+impl From<ipfs_api_backend_hyper::Error> for Error {
+	fn from(error: ipfs_api_backend_hyper::Error) -> Self {
+		Self::IPFS(error)
+	}
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+	fn from(error: std::string::FromUtf8Error) -> Self {
+		Self::FromUtf8(error)
+	}
+}
+
+/// What the library was doing when an error happened
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operation {
+	ReadThread,
+	ParseThread,
+	WriteThread,
+	CreateThread,
+	ReadMessage,
+	ReadForum,
+	ParseForum,
+	WriteForum,
+	CreateForum,
+	DeleteKey,
+	ReadAccount,
+	WriteAccount,
+}
+
+impl fmt::Display for Operation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::ReadThread => write!(f, "reading thread"),
+			Self::ParseThread => write!(f, "parsing thread"),
+			Self::WriteThread => write!(f, "writing to thread"),
+			Self::CreateThread => write!(f, "creating thread"),
+			Self::ReadMessage => write!(f, "reading message"),
+			Self::ReadForum => write!(f, "reading forum"),
+			Self::ParseForum => write!(f, "parsing forum"),
+			Self::WriteForum => write!(f, "writing to forum"),
+			Self::CreateForum => write!(f, "creating forum"),
+			Self::DeleteKey => write!(f, "deleting key"),
+			Self::ReadAccount => write!(f, "reading account file"),
+			Self::WriteAccount => write!(f, "writing account file"),
 		}
 	}
 }
@@ -39,6 +196,18 @@ pub struct Message {
 }
 
 impl Message {
+	/// Create a message replying to `prev_hash` signed with the keypair
+	pub fn new_signed(body: String, prev_hash: [u8; 64], keypair: &ed25519_dalek::Keypair) -> Self {
+		let to_sign = &[body.as_bytes(), &prev_hash].concat();
+		let signature = keypair.sign(to_sign);
+		Self {
+			prev_hash,
+			public_key: keypair.public,
+			body,
+			signature,
+		}
+	}
+
 	// Logic methods
 	pub fn get_hash(&self) -> [u8; 64] {
 		let mut collection_vector = Vec::<u8>::new();
@@ -79,7 +248,7 @@ impl Message {
 		Self::encode_base64(bytes)
 	}
 
-	fn encode_base64(bytes: &[u8]) -> String {
+	pub(crate) fn encode_base64(bytes: &[u8]) -> String {
 		general_purpose::STANDARD_NO_PAD.encode(bytes)
 	}
 
@@ -95,11 +264,97 @@ impl Message {
 	}
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Default)]
+/// Why a message from the file was or wasn't accepted
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MessageStatus {
+	/// Decoded and properly signed
+	Valid,
+	BadBase64(MessageField),
+	WrongLength(MessageField),
+	InvalidPublicKey,
+	InvalidSignature,
+	/// Decoded but the signature doesn't match the public key
+	BadSignature,
+}
+
+impl fmt::Display for MessageStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Valid => write!(f, "Valid"),
+			Self::BadBase64(field) => write!(f, "Bad base64 in {field}"),
+			Self::WrongLength(field) => write!(f, "Wrong length of {field}"),
+			Self::InvalidPublicKey => write!(f, "Invalid public key"),
+			Self::InvalidSignature => write!(f, "Invalid signature"),
+			Self::BadSignature => write!(f, "Bad signature"),
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MessageField {
+	PrevHash,
+	PublicKey,
+	Signature,
+}
+
+impl fmt::Display for MessageField {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::PrevHash => write!(f, "prev_hash"),
+			Self::PublicKey => write!(f, "public_key"),
+			Self::Signature => write!(f, "signature"),
+		}
+	}
+}
+
+/// A message from the file along with the result of checking it
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerifiedMessage {
+	pub file_message: FileMessage,
+	/// `None` if the message couldn't be decoded
+	pub message: Option<Message>,
+	pub status: MessageStatus,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug, PartialEq, Clone)]
 pub struct Header {
 	pub name: String,
 	pub thread_number: u32,
 	pub tags: Vec<String>,
+	/// IPNS links to copies of the thread published by contributors
+	pub mirrors: Vec<String>,
+	/// Base64 public key of whoever controls the member list, empty if nobody does yet
+	pub owner: String,
+	/// Every version of the member list, see `members::Membership` for which one counts
+	pub member_lists: Vec<MemberList>,
+	/// Every version of the fields above signed by the owner, the last one is used when reading
+	pub revisions: Vec<HeaderRevision>,
+}
+
+/// A version of the header's name, number, tags and mirrors
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+pub struct HeaderRevision {
+	/// Where it is in `Header::revisions`, so an old revision can't be copied to the end
+	pub revision: u32,
+	pub name: String,
+	pub thread_number: u32,
+	pub tags: Vec<String>,
+	pub mirrors: Vec<String>,
+	/// Base64 public key of the owner
+	pub signed_by: String,
+	pub signature: String,
+}
+
+/// A version of the keys allowed to post in a thread
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+pub struct MemberList {
+	/// Base64 public keys
+	pub members: Vec<String>,
+	/// Base64 public keys of those allowed to sign the next version
+	pub moderators: Vec<String>,
+	/// Base64 public key of the owner or moderator that signed this version
+	pub signed_by: String,
+	pub signature: String,
 }
 
 impl Header {
@@ -108,11 +363,15 @@ impl Header {
 			name: String::new(),
 			thread_number: 0,
 			tags: Vec::<String>::new(),
+			mirrors: Vec::<String>::new(),
+			owner: String::new(),
+			member_lists: Vec::<MemberList>::new(),
+			revisions: Vec::<HeaderRevision>::new(),
 		}
 	}
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
 pub struct FileMessage {
 	pub prev_hash: String,
 	pub public_key: String,
@@ -120,21 +379,96 @@ pub struct FileMessage {
 	pub signature: String,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
 pub struct FullFile {
+	/// The layout the file was written in, see `migrate` for how older ones are read
+	pub format_version: u32,
 	pub header: Header,
+	/// The newest message of each chain if messages are stored as their own objects (see
+	/// `linked`), `None` if they are all in `messages`
+	pub heads: Option<Vec<MessageHead>>,
+	/// When reading this also has the messages fetched from `heads`, after the ones in the file
 	pub messages: Vec<FileMessage>,
 }
 
+/// A message stored as its own object, the newest in its chain
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+pub struct MessageHead {
+	/// Content path of the `LinkedMessage`
+	pub path: String,
+	/// Base64 hash of the message, so replying to it doesn't need it to be fetched, `None` if the
+	/// message can't be decoded as it can't be replied to
+	pub hash: Option<String>,
+}
+
+/// A message stored as its own object along with where the message it replies to is stored
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+pub struct LinkedMessage {
+	/// Content path of the `LinkedMessage` that `prev_hash` refers to, `None` if it isn't stored
+	pub prev: Option<String>,
+	pub message: FileMessage,
+}
+
 impl FullFile {
 	pub fn new() -> Self {
 		Self {
+			format_version: crate::migrate::FORMAT_VERSION,
 			header: Header::new(),
+			heads: None,
 			messages: Vec::<FileMessage>::new(),
 		}
 	}
 }
 
+impl Default for FullFile {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// The file an IPNS forum link points to, everything needed to find the rest of the forum
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug, PartialEq, Clone)]
+pub struct ForumManifest {
+	pub name: String,
+	/// IPNS links to the threads in the forum
+	pub threads: Vec<String>,
+	/// Where each user's profile is published
+	pub users: Vec<ProfileLink>,
+	/// Base64 public keys of those trusted to moderate the forum's threads, this is only
+	/// informational as each thread's member lists decide who can moderate it
+	pub moderators: Vec<String>,
+	/// IPNS links to copies of the manifest published by contributors
+	pub mirrors: Vec<String>,
+	/// Base64 public key that signed the manifest, only they can sign the next version
+	pub owner: String,
+	pub signature: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+pub struct ProfileLink {
+	/// Base64 public key of the user
+	pub public_key: String,
+	/// IPNS link to their profile
+	pub link: String,
+}
+
+/// Whether `signature` is a valid signature of `data` by `public_key`, both in base64
+pub(crate) fn is_signed_by(public_key: &str, signature: &str, data: &[u8]) -> bool {
+	let base64 = general_purpose::STANDARD_NO_PAD;
+	let public_key = base64
+		.decode(public_key)
+		.ok()
+		.and_then(|bytes| ed25519_dalek::PublicKey::from_bytes(&bytes).ok());
+	let signature = base64
+		.decode(signature)
+		.ok()
+		.and_then(|bytes| ed25519_dalek::Signature::from_bytes(&bytes).ok());
+	match (public_key, signature) {
+		(Some(public_key), Some(signature)) => public_key.verify(data, &signature).is_ok(),
+		_ => false,
+	}
+}
+
 pub fn ipns_link_to_key(link: &str) -> String {
 	format!("GitArkRepo{link}")
 }
@@ -0,0 +1,208 @@
+//! The formats a thread file can be stored in
+//!
+//! TOML is easy to read and edit by hand. The binary format is MessagePack with the hashes, keys
+//! and signatures stored as raw bytes instead of base64, so it is smaller and quicker to parse
+//! (see `docs/Binary vs Plaintext`). Binary files start with `MAGIC` and a version number so they
+//! can be told apart from TOML when reading and changed later without breaking old files.
+//!
+//! Binary files were added in format version 1, so unlike TOML they aren't upgraded by `migrate`.
+//! If the layout changes the binary version should be bumped and the old layout kept here so it can
+//! still be decoded. Messages stored as their own objects (see `linked`) are encoded the same way
+//! as the thread they are in.
+use crate::{custom_types::*, migrate};
+use base64::{engine::general_purpose, Engine};
+
+const MAGIC: &[u8] = b"CFBIN";
+/// The version of the binary format that is written, bumped whenever its layout changes
+pub const BINARY_VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum FileFormat {
+	#[default]
+	Toml,
+	Binary,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BinaryFile {
+	format_version: u32,
+	header: Header,
+	heads: Option<Vec<MessageHead>>,
+	messages: Vec<BinaryMessage>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BinaryLinkedMessage {
+	prev: Option<String>,
+	message: BinaryMessage,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+enum BinaryMessage {
+	Raw {
+		#[serde(with = "serde_bytes")]
+		prev_hash: Vec<u8>,
+		#[serde(with = "serde_bytes")]
+		public_key: Vec<u8>,
+		body: String,
+		#[serde(with = "serde_bytes")]
+		signature: Vec<u8>,
+	},
+	/// Messages with fields that aren't valid base64 are kept as they were, so converting a file
+	/// never hides that it has been tampered with
+	Text(FileMessage),
+}
+
+pub fn detect_format(contents: &[u8]) -> FileFormat {
+	match contents.starts_with(MAGIC) {
+		true => FileFormat::Binary,
+		false => FileFormat::Toml,
+	}
+}
+
+/// Encode the file exactly as it is, header revisions aren't applied or checked
+pub fn encode(file: &FullFile, format: FileFormat) -> Result<Vec<u8>, Error> {
+	match format {
+		FileFormat::Toml => Ok(toml::to_string(file)?.into_bytes()),
+		FileFormat::Binary => {
+			let binary = BinaryFile {
+				format_version: file.format_version,
+				header: file.header.clone(),
+				heads: file.heads.clone(),
+				messages: file.messages.iter().cloned().map(to_binary).collect(),
+			};
+			encode_binary(&binary)
+		}
+	}
+}
+
+/// Encode a message to be stored as its own object
+pub fn encode_message(message: &LinkedMessage, format: FileFormat) -> Result<Vec<u8>, Error> {
+	match format {
+		FileFormat::Toml => Ok(toml::to_string(message)?.into_bytes()),
+		FileFormat::Binary => encode_binary(&BinaryLinkedMessage {
+			prev: message.prev.clone(),
+			message: to_binary(message.message.clone()),
+		}),
+	}
+}
+
+pub fn decode_message(contents: &[u8]) -> Result<LinkedMessage, Error> {
+	match detect_format(contents) {
+		FileFormat::Toml => Ok(toml::from_str(&String::from_utf8(contents.to_vec())?)?),
+		FileFormat::Binary => {
+			let contents = binary_contents(contents)?;
+			let binary: BinaryLinkedMessage = rmp_serde::from_slice(contents)?;
+			Ok(LinkedMessage {
+				prev: binary.prev,
+				message: from_binary(binary.message),
+			})
+		}
+	}
+}
+
+fn encode_binary(value: &impl serde::Serialize) -> Result<Vec<u8>, Error> {
+	let mut contents = MAGIC.to_vec();
+	contents.push(BINARY_VERSION);
+	rmp_serde::encode::write(&mut contents, value)?;
+	Ok(contents)
+}
+
+/// What comes after the binary version, if it's the version that is written
+fn binary_contents(contents: &[u8]) -> Result<&[u8], Error> {
+	let Some((&version, contents)) = contents[MAGIC.len()..].split_first() else {
+		return Err(Error::InvalidFileData(
+			"binary file has no version".to_string(),
+		));
+	};
+	if version != BINARY_VERSION {
+		let message = format!("binary format version {version} isn't supported");
+		return Err(Error::InvalidFileData(message));
+	}
+	Ok(contents)
+}
+
+/// Decode a file in either format and upgrade it to the newest layout
+///
+/// An empty file is a thread with no header or messages.
+pub fn decode(contents: &[u8]) -> Result<FullFile, Error> {
+	if contents.is_empty() {
+		return Ok(FullFile::new());
+	}
+	match detect_format(contents) {
+		FileFormat::Toml => migrate::upgrade(&String::from_utf8(contents.to_vec())?),
+		FileFormat::Binary => Ok(FullFile {
+			format_version: migrate::FORMAT_VERSION,
+			..decode_binary(contents)?
+		}),
+	}
+}
+
+/// The format version the file was written in, before it is upgraded by `decode`
+pub fn stored_version(contents: &[u8]) -> Result<u32, Error> {
+	if contents.is_empty() {
+		return Ok(migrate::FORMAT_VERSION);
+	}
+	match detect_format(contents) {
+		FileFormat::Toml => migrate::stored_version(&String::from_utf8(contents.to_vec())?),
+		FileFormat::Binary => Ok(decode_binary(contents)?.format_version),
+	}
+}
+
+/// Decode a binary file as it was stored, without changing its format version
+fn decode_binary(contents: &[u8]) -> Result<FullFile, Error> {
+	let file: BinaryFile = rmp_serde::from_slice(binary_contents(contents)?)?;
+	migrate::check_version(file.format_version)?;
+	Ok(FullFile {
+		format_version: file.format_version,
+		header: file.header,
+		heads: file.heads,
+		messages: file.messages.into_iter().map(from_binary).collect(),
+	})
+}
+
+/// Re-encode a file in another format without changing what it contains
+pub fn convert(contents: &[u8], format: FileFormat) -> Result<Vec<u8>, Error> {
+	encode(&decode(contents)?, format)
+}
+
+fn to_binary(message: FileMessage) -> BinaryMessage {
+	let raw = (
+		decode_exact(&message.prev_hash),
+		decode_exact(&message.public_key),
+		decode_exact(&message.signature),
+	);
+	match raw {
+		(Some(prev_hash), Some(public_key), Some(signature)) => BinaryMessage::Raw {
+			prev_hash,
+			public_key,
+			body: message.body,
+			signature,
+		},
+		_ => BinaryMessage::Text(message),
+	}
+}
+
+fn from_binary(message: BinaryMessage) -> FileMessage {
+	match message {
+		BinaryMessage::Raw {
+			prev_hash,
+			public_key,
+			body,
+			signature,
+		} => FileMessage {
+			prev_hash: Message::encode_base64(&prev_hash),
+			public_key: Message::encode_base64(&public_key),
+			body,
+			signature: Message::encode_base64(&signature),
+		},
+		BinaryMessage::Text(message) => message,
+	}
+}
+
+/// The bytes if encoding them again gives back the same string
+fn decode_exact(string: &str) -> Option<Vec<u8>> {
+	let base64 = general_purpose::STANDARD_NO_PAD;
+	let bytes = base64.decode(string).ok()?;
+	(base64.encode(&bytes) == string).then_some(bytes)
+}
@@ -0,0 +1,208 @@
+//! A forum is an IPNS link to a signed manifest of its threads, users, moderators and mirrors
+//!
+//! This is the `threads` and `users` directories from `docs/File Structure.md`. IPFS directories
+//! can't be changed in place, so the manifest is a file published the same way threads are and
+//! every change republishes it signed by the forum's owner.
+//!
+//! The signature covers the forum's link, so a manifest can't be passed off as another forum's.
+//! A manifest only proves it was signed by the owner it lists though, so anyone could publish one
+//! of their own. Readers that already know the owner, from reading the forum before or from
+//! whoever shared it, should pass it in, and copies read from mirrors are always checked against it.
+//!
+//! The moderators are only informational, who can moderate a thread is decided by the thread's own
+//! member lists (see `members`).
+use crate::{custom_types::*, read, store::strip_ipns_prefix, store::ForumStore, write};
+use ed25519_dalek::{Keypair, Signer};
+use futures::future::join_all;
+
+/// A thread listed in a forum, along with its header if it could be read
+#[derive(Debug)]
+pub struct ForumThread {
+	pub link: String,
+	pub header: Result<Header, Error>,
+}
+
+impl ForumManifest {
+	/// A manifest with no threads, users, moderators or mirrors, it's signed by `new_forum`
+	pub fn new(name: String) -> Self {
+		Self {
+			name,
+			..Self::default()
+		}
+	}
+
+	/// Make the keypair the owner and sign the current contents for the forum at `link`
+	pub fn sign(&mut self, link: &str, keypair: &Keypair) {
+		self.owner = Message::encode_base64(keypair.public.as_bytes());
+		let signature = keypair.sign(&self.signed_data(link));
+		self.signature = Message::encode_base64(&signature.to_bytes());
+	}
+
+	/// Whether the signature matches `owner` for the forum at `link`
+	pub fn is_signed(&self, link: &str) -> bool {
+		is_signed_by(&self.owner, &self.signature, &self.signed_data(link))
+	}
+
+	fn signed_data(&self, link: &str) -> Vec<u8> {
+		let fields = (
+			strip_ipns_prefix(link),
+			&self.name,
+			&self.threads,
+			&self.users,
+			&self.moderators,
+			&self.mirrors,
+		);
+		serde_json::to_vec(&fields).expect("Only has string keys so it can always be serialized")
+	}
+}
+
+/// Publishes the manifest to a new IPNS link signed by the keypair, returns the link to it
+pub async fn new_forum(
+	store: &impl ForumStore,
+	manifest: &ForumManifest,
+	keypair: &Keypair,
+) -> Result<String, Error> {
+	let temp_key = "ThisIsATempForumNameIfYouAreSeeingThisSomethingWentWrong";
+	let mut manifest = manifest.clone();
+	let name = format!("\"{}\"", manifest.name);
+	write::create_ipns(store, temp_key, |link| {
+		manifest.sign(link, keypair);
+		manifest
+	})
+	.await
+	.map_err(|e| e.context(Operation::CreateForum, name))
+}
+
+/// Read the manifest, which fails if it isn't signed for the link by the owner it lists, or by
+/// `owner` if it's given
+pub async fn get_manifest(
+	store: &impl ForumStore,
+	link: &str,
+	owner: Option<&str>,
+) -> Result<ForumManifest, Error> {
+	read_manifest(store, link, link, owner).await
+}
+
+/// Read a copy of the forum's manifest from one of its mirrors, for when the forum's own link
+/// can't be read
+///
+/// Anyone can publish a mirror, so the copy must be signed for the forum's link by its owner.
+pub async fn get_mirrored_manifest(
+	store: &impl ForumStore,
+	forum_link: &str,
+	mirror: &str,
+	owner: &str,
+) -> Result<ForumManifest, Error> {
+	read_manifest(store, mirror, forum_link, Some(owner)).await
+}
+
+async fn read_manifest(
+	store: &impl ForumStore,
+	source: &str,
+	forum_link: &str,
+	owner: Option<&str>,
+) -> Result<ForumManifest, Error> {
+	let contents = read::read_file_bytes(store, source)
+		.await
+		.map_err(|e| e.context(Operation::ReadForum, source))?;
+	parse_manifest(&contents, forum_link, owner)
+		.map_err(|e| e.context(Operation::ParseForum, source))
+}
+
+fn parse_manifest(
+	contents: &str,
+	forum_link: &str,
+	owner: Option<&str>,
+) -> Result<ForumManifest, Error> {
+	let manifest: ForumManifest = toml::from_str(contents)?;
+	if !manifest.is_signed(forum_link) {
+		let message = "the forum manifest isn't signed by its owner".to_string();
+		return Err(Error::InvalidFileData(message));
+	}
+	if owner.is_some_and(|owner| owner != manifest.owner) {
+		let message = format!("the forum manifest is signed by {} instead", manifest.owner);
+		return Err(Error::Unauthorized(message));
+	}
+	Ok(manifest)
+}
+
+/// Replace the manifest with a new version, which must be signed by the current owner
+pub async fn write_manifest(
+	store: &impl ForumStore,
+	link: &str,
+	manifest: &ForumManifest,
+) -> Result<(), Error> {
+	let current = get_manifest(store, link, None).await?;
+	if !manifest.is_signed(link) || manifest.owner != current.owner {
+		let message = format!("{} isn't the owner of {link}", manifest.owner);
+		return Err(Error::Unauthorized(message));
+	}
+	upload_manifest(store, link, manifest)
+		.await
+		.map_err(|e| e.context(Operation::WriteForum, link))
+}
+
+async fn upload_manifest(
+	store: &impl ForumStore,
+	link: &str,
+	manifest: &ForumManifest,
+) -> Result<(), Error> {
+	let contents = toml::to_string(manifest)?.into_bytes();
+	write::upload_to_ipns(store, &ipns_link_to_key(link), contents).await
+}
+
+/// Add the thread to the end of the forum, nothing happens if it is already there
+pub async fn add_thread(
+	store: &impl ForumStore,
+	forum_link: &str,
+	thread_link: &str,
+	keypair: &Keypair,
+) -> Result<(), Error> {
+	let mut manifest = get_manifest(store, forum_link, None).await?;
+	if manifest.threads.iter().any(|thread| thread == thread_link) {
+		return Ok(());
+	}
+	manifest.threads.push(thread_link.to_string());
+	manifest.sign(forum_link, keypair);
+	write_manifest(store, forum_link, &manifest).await
+}
+
+/// Read the header of every thread in the forum, ordered by thread number
+///
+/// Threads that couldn't be read are put at the end instead of stopping the others being listed.
+/// See `get_manifest` for `owner`.
+pub async fn list_threads(
+	store: &impl ForumStore,
+	link: &str,
+	owner: Option<&str>,
+) -> Result<Vec<ForumThread>, Error> {
+	let manifest = get_manifest(store, link, owner).await?;
+	let headers = join_all(
+		manifest
+			.threads
+			.iter()
+			.map(|thread| read::get_header(store, thread)),
+	)
+	.await;
+	let mut threads: Vec<ForumThread> = manifest
+		.threads
+		.into_iter()
+		.zip(headers)
+		.map(|(link, header)| ForumThread { link, header })
+		.collect();
+	threads.sort_by_key(|thread| match &thread.header {
+		Ok(header) => (false, header.thread_number),
+		Err(_) => (true, 0),
+	});
+	Ok(threads)
+}
+
+/// The first thread with the number, threads that couldn't be read are never found
+pub fn find_thread(threads: &[ForumThread], thread_number: u32) -> Option<&ForumThread> {
+	threads.iter().find(|thread| {
+		thread
+			.header
+			.as_ref()
+			.is_ok_and(|header| header.thread_number == thread_number)
+	})
+}
@@ -0,0 +1,94 @@
+//! Signed revisions of a thread's header
+//!
+//! The name, number, tags and mirrors in the header aren't trusted on their own, instead every
+//! edit is appended to `Header::revisions` and readers use the last one signed by the owner. The
+//! owner is whoever signed the first revision, `Header::owner` is only a copy of it, and every
+//! revision also signs the thread's link so it can't be copied into another thread. This means
+//! republishing the file can't rename, retag or take over the thread, only leave out recent edits
+//! or drop every revision along with the owner and member lists, which leaves an unsigned header.
+use crate::{custom_types::*, store::strip_ipns_prefix};
+use ed25519_dalek::{Keypair, Signer};
+
+impl HeaderRevision {
+	/// Sign the header's current fields as the revision after its last one for the thread at `link`
+	pub fn new_signed(header: &Header, link: &str, keypair: &Keypair) -> Self {
+		let mut revision = Self {
+			revision: header.revisions.len() as u32,
+			name: header.name.clone(),
+			thread_number: header.thread_number,
+			tags: header.tags.clone(),
+			mirrors: header.mirrors.clone(),
+			signed_by: Message::encode_base64(keypair.public.as_bytes()),
+			signature: String::new(),
+		};
+		let signature = keypair.sign(&revision.signed_data(link));
+		revision.signature = Message::encode_base64(&signature.to_bytes());
+		revision
+	}
+
+	/// Whether the signature matches `signed_by` for the thread at `link`, this doesn't check
+	/// `signed_by` is the owner
+	pub fn is_signed(&self, link: &str) -> bool {
+		is_signed_by(&self.signed_by, &self.signature, &self.signed_data(link))
+	}
+
+	fn signed_data(&self, link: &str) -> Vec<u8> {
+		let fields = (
+			strip_ipns_prefix(link),
+			self.revision,
+			&self.name,
+			self.thread_number,
+			&self.tags,
+			&self.mirrors,
+		);
+		serde_json::to_vec(&fields).expect("Only has string keys so it can always be serialized")
+	}
+}
+
+/// Replace the header's fields with the last revision signed by the owner
+///
+/// Headers without any revisions are left as they are, as long as they don't claim to have an
+/// owner or member lists. Otherwise the first revision decides who the owner is and must be
+/// properly signed.
+pub fn apply_revisions(header: &mut Header, link: &str) -> Result<(), Error> {
+	let Some(first) = header.revisions.first() else {
+		if !header.owner.is_empty() || !header.member_lists.is_empty() {
+			let message =
+				"the header has an owner or member lists but no signed revisions".to_string();
+			return Err(Error::InvalidFileData(message));
+		}
+		return Ok(());
+	};
+	let owner = first.signed_by.clone();
+	if !is_valid(&owner, link, 0, first) {
+		let message = "the first header revision isn't properly signed".to_string();
+		return Err(Error::InvalidFileData(message));
+	}
+	if !header.owner.is_empty() && header.owner != owner {
+		let message = "the header's owner didn't sign its first revision".to_string();
+		return Err(Error::InvalidFileData(message));
+	}
+	let latest = header
+		.revisions
+		.iter()
+		.enumerate()
+		.rev()
+		.find(|(index, revision)| is_valid(&owner, link, *index, revision))
+		.map(|(_, revision)| revision.clone())
+		.expect("The first revision is valid");
+	header.owner = owner;
+	header.name = latest.name;
+	header.thread_number = latest.thread_number;
+	header.tags = latest.tags;
+	header.mirrors = latest.mirrors;
+	Ok(())
+}
+
+/// Whether `signed_by` can add a revision, anyone can if the thread has no owner yet
+pub fn can_edit(header: &Header, signed_by: &str) -> bool {
+	header.owner.is_empty() || header.owner == signed_by
+}
+
+fn is_valid(owner: &str, link: &str, index: usize, revision: &HeaderRevision) -> bool {
+	revision.revision as usize == index && revision.signed_by == owner && revision.is_signed(link)
+}
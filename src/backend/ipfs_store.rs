@@ -0,0 +1,151 @@
+use crate::{custom_types::Error, store::ForumStore};
+use futures::TryStreamExt;
+use ipfs_api_backend_hyper::{IpfsApi, IpfsClient, KeyType, TryFromUri};
+use std::{future::Future, io::Cursor, time::Duration};
+
+/// How to connect to the IPFS daemon
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpfsConfig {
+	/// The address of the daemon's HTTP API
+	pub api_url: String,
+	/// How long to wait for each request, `None` waits forever
+	pub timeout: Option<Duration>,
+	/// How many extra attempts to make when a request that is safe to repeat fails
+	pub retries: u32,
+	/// How long to wait between attempts
+	pub retry_delay: Duration,
+	/// Username and password for basic auth
+	pub credentials: Option<(String, String)>,
+}
+
+impl Default for IpfsConfig {
+	fn default() -> Self {
+		Self {
+			api_url: "http://localhost:5001".to_string(),
+			timeout: Some(Duration::from_secs(60)),
+			retries: 0,
+			retry_delay: Duration::from_secs(1),
+			credentials: None,
+		}
+	}
+}
+
+/// A store backed by an IPFS daemon
+pub struct IpfsStore {
+	client: IpfsClient,
+	config: IpfsConfig,
+}
+
+impl IpfsStore {
+	/// Connects to the IPFS daemon at the default address (localhost:5001)
+	pub fn new() -> Result<Self, Error> {
+		Self::with_config(IpfsConfig::default())
+	}
+
+	pub fn with_config(config: IpfsConfig) -> Result<Self, Error> {
+		let client = IpfsClient::from_str(&config.api_url)
+			.map_err(|e| Error::InvalidConfig(format!("{}: {e}", config.api_url)))?;
+		let client = match &config.credentials {
+			Some((username, password)) => client.with_credentials(username, password),
+			None => client,
+		};
+		Ok(Self { client, config })
+	}
+
+	/// Run the request, retrying it according to the config if it fails
+	async fn request<T, F, Fut>(&self, make_request: F) -> Result<T, Error>
+	where
+		F: Fn() -> Fut,
+		Fut: Future<Output = Result<T, ipfs_api_backend_hyper::Error>>,
+	{
+		let mut attempts_left = self.config.retries;
+		loop {
+			let error = match self.request_once(make_request()).await {
+				Ok(res) => return Ok(res),
+				Err(e) => e,
+			};
+			if attempts_left == 0 {
+				return Err(error);
+			}
+			attempts_left -= 1;
+			tokio::time::sleep(self.config.retry_delay).await;
+		}
+	}
+
+	/// Run the request a single time, for requests that are not safe to repeat
+	async fn request_once<T, Fut>(&self, request: Fut) -> Result<T, Error>
+	where
+		Fut: Future<Output = Result<T, ipfs_api_backend_hyper::Error>>,
+	{
+		let timeout = match self.config.timeout {
+			Some(timeout) => timeout,
+			None => return request.await.map_err(Error::IPFS),
+		};
+		match tokio::time::timeout(timeout, request).await {
+			Ok(result) => result.map_err(Error::IPFS),
+			Err(_) => Err(Error::Timeout(timeout)),
+		}
+	}
+}
+
+impl ForumStore for IpfsStore {
+	async fn resolve(&self, name: &str) -> Result<String, Error> {
+		let result = self
+			.request(|| self.client.name_resolve(Some(name), true, false))
+			.await
+			.map_err(|e| resolve_error(e, name))?;
+		Ok(result.path)
+	}
+
+	async fn cat(&self, path: &str) -> Result<Vec<u8>, Error> {
+		self.request(|| {
+			self.client
+				.cat(path)
+				.map_ok(|chunk| chunk.to_vec())
+				.try_concat()
+		})
+		.await
+	}
+
+	async fn add(&self, data: Vec<u8>) -> Result<String, Error> {
+		let result = self
+			.request(|| self.client.add(Cursor::new(data.clone())))
+			.await?;
+		Ok(format!("/ipfs/{}", result.name))
+	}
+
+	async fn publish(&self, key: &str, path: &str) -> Result<(), Error> {
+		self.request(|| self.client.name_publish(path, false, None, None, Some(key)))
+			.await?;
+		Ok(())
+	}
+
+	async fn key_gen(&self, key: &str) -> Result<String, Error> {
+		let result = self
+			.request_once(self.client.key_gen(key, KeyType::Ed25519, 64))
+			.await?;
+		Ok(result.id)
+	}
+
+	async fn key_rename(&self, key: &str, new_key: &str) -> Result<(), Error> {
+		self.request_once(self.client.key_rename(key, new_key, false))
+			.await?;
+		Ok(())
+	}
+
+	async fn key_rm(&self, key: &str) -> Result<(), Error> {
+		self.request_once(self.client.key_rm(key)).await?;
+		Ok(())
+	}
+}
+
+/// The daemon answering with an error means it was reached but the name couldn't be resolved, so
+/// it's reported the same as an unknown link in the other stores
+fn resolve_error(error: Error, name: &str) -> Error {
+	match error {
+		Error::IPFS(ipfs_api_backend_hyper::Error::Api(e)) => {
+			Error::NotFound(format!("{name}: {}", e.message))
+		}
+		error => error,
+	}
+}
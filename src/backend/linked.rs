@@ -0,0 +1,172 @@
+//! Threads where every message is its own object
+//!
+//! Each `LinkedMessage` has the content path of the message it replies to, and the thread file
+//! only lists the newest message of each chain in `FullFile::heads`. Posting a reply to the end of
+//! a chain uploads the message and a thread file that doesn't grow with the thread, and as objects
+//! never change a `MessageCache` means reading the thread again only fetches the new messages.
+//! The hashes in `prev_hash` are still what the messages are checked against, the content paths
+//! are only used to find them.
+use crate::{
+	chains::ROOT_HASH,
+	custom_types::*,
+	encoding::{self, FileFormat},
+	read,
+	store::ForumStore,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Messages that have already been fetched, by content path
+#[derive(Default)]
+pub struct MessageCache {
+	messages: HashMap<String, LinkedMessage>,
+	/// Hash of each message that could be decoded to its content path, worked out once when
+	/// it's added as checking the signature is slow
+	paths: HashMap<String, String>,
+}
+
+impl MessageCache {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn len(&self) -> usize {
+		self.messages.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.messages.is_empty()
+	}
+
+	async fn get(&mut self, store: &impl ForumStore, path: &str) -> Result<&LinkedMessage, Error> {
+		if !self.messages.contains_key(path) {
+			let contents = store.cat(path).await?;
+			let message = encoding::decode_message(&contents)?;
+			self.insert(path.to_string(), message);
+		}
+		Ok(&self.messages[path])
+	}
+
+	/// Add the message and return its hash, messages that can't be decoded don't have one
+	fn insert(&mut self, path: String, linked: LinkedMessage) -> Option<String> {
+		let hash = hash_of(&linked.message);
+		if let Some(hash) = &hash {
+			self.paths.insert(hash.clone(), path.clone());
+		}
+		self.messages.insert(path, linked);
+		hash
+	}
+
+	/// The path of a fetched message with the hash
+	fn find(&self, hash: &str) -> Option<String> {
+		self.paths.get(hash).cloned()
+	}
+}
+
+/// Every message reachable from the heads, each after the one it replies to
+///
+/// Only messages that aren't in the cache are fetched.
+pub async fn fetch_messages(
+	store: &impl ForumStore,
+	heads: &[MessageHead],
+	cache: &mut MessageCache,
+) -> Result<Vec<FileMessage>, Error> {
+	let mut messages = Vec::new();
+	let mut done = HashSet::new();
+	for head in heads {
+		// Newest first, stopping at a message already added from another chain
+		let mut chain = Vec::new();
+		let mut next = Some(head.path.clone());
+		while let Some(path) = next.filter(|path| !done.contains(path)) {
+			let linked = cache
+				.get(store, &path)
+				.await
+				.map_err(|e| e.context(Operation::ReadMessage, &path))?;
+			next = linked.prev.clone();
+			chain.push(path);
+		}
+		for path in chain.into_iter().rev() {
+			messages.push(cache.messages[&path].message.clone());
+			done.insert(path);
+		}
+	}
+	Ok(messages)
+}
+
+/// The hash of the newest message of each chain, fetching only the heads
+///
+/// `None` if a head isn't validly signed, as its chain then ends somewhere before it and the whole
+/// thread has to be fetched to find where. The messages before the heads aren't checked.
+pub async fn chain_ends(
+	store: &impl ForumStore,
+	heads: &[MessageHead],
+	cache: &mut MessageCache,
+) -> Result<Option<Vec<[u8; 64]>>, Error> {
+	let mut ends = Vec::new();
+	for head in heads {
+		let linked = cache
+			.get(store, &head.path)
+			.await
+			.map_err(|e| e.context(Operation::ReadMessage, &head.path))?;
+		match read::verify_message(linked.message.clone()).message {
+			Some(message) if message.is_signed() => ends.push(message.get_hash()),
+			_ => return Ok(None),
+		}
+	}
+	Ok(Some(ends))
+}
+
+/// Store each message as its own object and update the heads
+///
+/// Replying to a head or to nothing doesn't need any messages to be fetched, replying to anything
+/// else forks the chain so the thread is fetched to find where the message it replies to is. It's
+/// an error to reply to a message that isn't in the thread, as the reply couldn't be linked to it.
+pub async fn append(
+	store: &impl ForumStore,
+	heads: &mut Vec<MessageHead>,
+	messages: Vec<FileMessage>,
+	format: FileFormat,
+	cache: &mut MessageCache,
+) -> Result<(), Error> {
+	for message in messages {
+		let prev = find_prev(store, heads, &message.prev_hash, cache).await?;
+		let linked = LinkedMessage { prev, message };
+		let path = store
+			.add(encoding::encode_message(&linked, format)?)
+			.await?;
+		heads.retain(|head| Some(&head.path) != linked.prev.as_ref());
+		let hash = cache.insert(path.clone(), linked);
+		heads.push(MessageHead { path, hash });
+	}
+	Ok(())
+}
+
+async fn find_prev(
+	store: &impl ForumStore,
+	heads: &[MessageHead],
+	prev_hash: &str,
+	cache: &mut MessageCache,
+) -> Result<Option<String>, Error> {
+	if prev_hash == Message::encode_base64(&ROOT_HASH) {
+		return Ok(None);
+	}
+	if let Some(head) = heads
+		.iter()
+		.find(|head| head.hash.as_deref() == Some(prev_hash))
+	{
+		return Ok(Some(head.path.clone()));
+	}
+	fetch_messages(store, heads, cache).await?;
+	match cache.find(prev_hash) {
+		Some(path) => Ok(Some(path)),
+		None => {
+			let message =
+				format!("the thread has no message with the hash {prev_hash} to reply to");
+			Err(Error::InvalidFileData(message))
+		}
+	}
+}
+
+fn hash_of(message: &FileMessage) -> Option<String> {
+	let verified = read::verify_message(message.clone());
+	verified.message.map(|message| message.hash_string())
+}
@@ -0,0 +1,136 @@
+use crate::{
+	custom_types::Error,
+	store::{content_path, random_name, strip_ipns_prefix, ForumStore},
+};
+use base64::{engine::general_purpose, Engine};
+use std::{
+	fs,
+	io::ErrorKind,
+	path::{Path, PathBuf},
+};
+
+/// A store kept in a directory so it can be used without an IPFS daemon (e.g. on a shared drive)
+///
+/// The directory is laid out as:
+/// - `ipfs/` the content, where each file name is the hash of its contents
+/// - `ipns/` the names, where each file contains the content path the name points to
+/// - `keys/` the keys, where each file contains the name the key controls
+pub struct LocalStore {
+	root: PathBuf,
+}
+
+impl LocalStore {
+	/// Uses the directory as the store, creating it if it does not exist
+	pub fn new(root: impl Into<PathBuf>) -> Result<Self, Error> {
+		let root = root.into();
+		for sub_dir in ["ipfs", "ipns", "keys"] {
+			fs::create_dir_all(root.join(sub_dir)).map_err(Error::StdIo)?;
+		}
+		Ok(Self { root })
+	}
+
+	fn content_file(&self, path: &str) -> Result<PathBuf, Error> {
+		let hash = path.strip_prefix("/ipfs/").unwrap_or(path);
+		self.file_in("ipfs", hash)
+	}
+
+	fn name_file(&self, name: &str) -> Result<PathBuf, Error> {
+		self.file_in("ipns", strip_ipns_prefix(name))
+	}
+
+	fn key_file(&self, key: &str) -> PathBuf {
+		// Key names contain slashes (see `ipns_link_to_key`) so encode them to get a valid file name
+		let file_name = general_purpose::URL_SAFE_NO_PAD.encode(key);
+		self.root.join("keys").join(file_name)
+	}
+
+	/// Makes sure the file name cannot point outside of the sub directory
+	fn file_in(&self, sub_dir: &str, file_name: &str) -> Result<PathBuf, Error> {
+		let is_plain_name = !file_name.is_empty()
+			&& file_name != "."
+			&& file_name != ".."
+			&& !file_name.contains(['/', '\\']);
+		if !is_plain_name {
+			return Err(Error::NotFound(file_name.to_string()));
+		}
+		Ok(self.root.join(sub_dir).join(file_name))
+	}
+}
+
+impl ForumStore for LocalStore {
+	async fn resolve(&self, name: &str) -> Result<String, Error> {
+		let file = self.name_file(name)?;
+		read_to_string(&file, name)
+	}
+
+	async fn cat(&self, path: &str) -> Result<Vec<u8>, Error> {
+		let file = self.content_file(path)?;
+		match fs::read(file) {
+			Ok(data) => Ok(data),
+			Err(e) if e.kind() == ErrorKind::NotFound => Err(Error::NotFound(path.to_string())),
+			Err(e) => Err(Error::StdIo(e)),
+		}
+	}
+
+	async fn add(&self, data: Vec<u8>) -> Result<String, Error> {
+		let path = content_path(&data);
+		let file = self.content_file(&path)?;
+		// The content is addressed by its hash so if it's already there it's already correct
+		if !file.exists() {
+			write_atomically(&file, &data)?;
+		}
+		Ok(path)
+	}
+
+	async fn publish(&self, key: &str, path: &str) -> Result<(), Error> {
+		let name = read_to_string(&self.key_file(key), key)?;
+		let file = self.name_file(&name)?;
+		write_atomically(&file, path.as_bytes())
+	}
+
+	async fn key_gen(&self, key: &str) -> Result<String, Error> {
+		let file = self.key_file(key);
+		if file.exists() {
+			return Err(Error::AlreadyExists(key.to_string()));
+		}
+		let name = random_name();
+		write_atomically(&file, name.as_bytes())?;
+		Ok(name)
+	}
+
+	async fn key_rename(&self, key: &str, new_key: &str) -> Result<(), Error> {
+		let old_file = self.key_file(key);
+		let new_file = self.key_file(new_key);
+		if !old_file.exists() {
+			return Err(Error::NotFound(key.to_string()));
+		}
+		if new_file.exists() {
+			return Err(Error::AlreadyExists(new_key.to_string()));
+		}
+		fs::rename(old_file, new_file).map_err(Error::StdIo)
+	}
+
+	async fn key_rm(&self, key: &str) -> Result<(), Error> {
+		match fs::remove_file(self.key_file(key)) {
+			Ok(()) => Ok(()),
+			Err(e) if e.kind() == ErrorKind::NotFound => Err(Error::NotFound(key.to_string())),
+			Err(e) => Err(Error::StdIo(e)),
+		}
+	}
+}
+
+fn read_to_string(file: &Path, looking_for: &str) -> Result<String, Error> {
+	match fs::read_to_string(file) {
+		Ok(contents) => Ok(contents.trim().to_string()),
+		Err(e) if e.kind() == ErrorKind::NotFound => Err(Error::NotFound(looking_for.to_string())),
+		Err(e) => Err(Error::StdIo(e)),
+	}
+}
+
+/// Write to a temporary file first then rename it so readers never see a half written file
+fn write_atomically(file: &Path, data: &[u8]) -> Result<(), Error> {
+	let mut temp_file = file.as_os_str().to_owned();
+	temp_file.push(format!(".{}.tmp", random_name()));
+	fs::write(&temp_file, data).map_err(Error::StdIo)?;
+	fs::rename(&temp_file, file).map_err(Error::StdIo)
+}
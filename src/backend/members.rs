@@ -0,0 +1,121 @@
+//! Who is allowed to post in a thread
+//!
+//! The header keeps every version of the member list, each signed by the thread's owner or by a
+//! moderator from the version before it. The last version signed by one of them is the current
+//! one and any other version is ignored. Each version also signs the thread's link and where it
+//! is in `Header::member_lists`, so an old version or one from another thread can't be appended
+//! again to undo a change.
+use crate::{custom_types::*, store::strip_ipns_prefix};
+use ed25519_dalek::{Keypair, Signer};
+
+/// Whether a message's key is allowed to post in the thread
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MemberStatus {
+	Owner,
+	Moderator,
+	Member,
+	/// The key isn't in the member list
+	Unknown,
+}
+
+impl MemberStatus {
+	pub fn is_member(self) -> bool {
+		self != Self::Unknown
+	}
+}
+
+/// The current members of a thread, taken from the last properly signed member list
+#[derive(Debug, PartialEq, Clone)]
+pub struct Membership {
+	pub owner: String,
+	pub moderators: Vec<String>,
+	pub members: Vec<String>,
+}
+
+impl Membership {
+	/// Get the current members, `None` if the thread has no owner or no valid member list
+	///
+	/// The header must have been read with its revisions applied, as that is what checks the owner
+	/// signed the first revision rather than it only being written in the file.
+	pub fn from_header(header: &Header, link: &str) -> Option<Self> {
+		current_list(header, link).map(|list| Self {
+			owner: header.owner.clone(),
+			moderators: list.moderators.clone(),
+			members: list.members.clone(),
+		})
+	}
+
+	pub fn status_of(&self, message: &Message) -> MemberStatus {
+		let public_key = message.public_key_string();
+		if public_key == self.owner {
+			MemberStatus::Owner
+		} else if self.moderators.contains(&public_key) {
+			MemberStatus::Moderator
+		} else if self.members.contains(&public_key) {
+			MemberStatus::Member
+		} else {
+			MemberStatus::Unknown
+		}
+	}
+}
+
+impl MemberList {
+	/// Make the next version of the thread's member list signed by the keypair, which must be the
+	/// owner's or a current moderator's
+	pub fn new_signed(
+		header: &Header,
+		link: &str,
+		members: Vec<String>,
+		moderators: Vec<String>,
+		keypair: &Keypair,
+	) -> Self {
+		let index = header.member_lists.len();
+		let signature = keypair.sign(&signed_data(link, index, &members, &moderators));
+		Self {
+			members,
+			moderators,
+			signed_by: Message::encode_base64(keypair.public.as_bytes()),
+			signature: Message::encode_base64(&signature.to_bytes()),
+		}
+	}
+
+	/// Whether the signature matches `signed_by` for the list at `index` in the thread at `link`,
+	/// this doesn't check `signed_by` is allowed to sign
+	pub fn is_signed(&self, link: &str, index: usize) -> bool {
+		let data = signed_data(link, index, &self.members, &self.moderators);
+		is_signed_by(&self.signed_by, &self.signature, &data)
+	}
+}
+
+/// The last version of the member list signed by the owner or a moderator from the version before
+pub(crate) fn current_list<'a>(header: &'a Header, link: &str) -> Option<&'a MemberList> {
+	if header.owner.is_empty() {
+		return None;
+	}
+	let mut current: Option<&MemberList> = None;
+	for (index, list) in header.member_lists.iter().enumerate() {
+		if can_sign(&header.owner, current, &list.signed_by) && list.is_signed(link, index) {
+			current = Some(list);
+		}
+	}
+	current
+}
+
+/// Whether `signed_by` can sign the version after `current`, the owner always can
+pub(crate) fn can_sign(owner: &str, current: Option<&MemberList>, signed_by: &str) -> bool {
+	signed_by == owner
+		|| current.is_some_and(|current| current.moderators.contains(&signed_by.to_string()))
+}
+
+/// The thread and position on the first line, then each key on its own line with a prefix
+/// saying which list it's in
+fn signed_data(link: &str, index: usize, members: &[String], moderators: &[String]) -> Vec<u8> {
+	let first_line = format!("thread {} list {index}\n", strip_ipns_prefix(link));
+	let moderator_lines = moderators.iter().map(|key| format!("moderator {key}\n"));
+	let member_lines = members.iter().map(|key| format!("member {key}\n"));
+	std::iter::once(first_line)
+		.chain(moderator_lines)
+		.chain(member_lines)
+		.collect::<String>()
+		.into_bytes()
+}
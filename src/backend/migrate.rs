@@ -0,0 +1,73 @@
+//! Upgrading thread files written in older layouts
+//!
+//! Every file has a `format_version`, files from before it existed are version 0. When reading,
+//! the file is parsed without a layout and each migration after its version is applied in order,
+//! so old threads can still be read without every field needing a default. Files from a newer
+//! version are refused instead of dropping the fields this version doesn't know about.
+use crate::custom_types::*;
+use toml::{Table, Value};
+
+/// The layout that is written, bump this and add to `MIGRATIONS` whenever `FullFile` changes
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Upgrades a file from the version before it
+type Migration = fn(&mut Table) -> Result<(), Error>;
+
+/// `MIGRATIONS[n]` upgrades a file from version `n` to `n + 1`
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [add_header_fields];
+
+/// Parse a TOML thread file of any version into the newest layout
+pub fn upgrade(contents: &str) -> Result<FullFile, Error> {
+	let mut file: Table = toml::from_str(contents)?;
+	let version = version_of(&file)?;
+	for migration in &MIGRATIONS[version as usize..] {
+		migration(&mut file)?;
+	}
+	file.insert("format_version".to_string(), Value::from(FORMAT_VERSION));
+	Ok(Value::Table(file).try_into()?)
+}
+
+/// The version a TOML thread file was written in
+pub fn stored_version(contents: &str) -> Result<u32, Error> {
+	version_of(&toml::from_str(contents)?)
+}
+
+/// Check a file can be read by this version
+pub fn check_version(version: u32) -> Result<(), Error> {
+	match version <= FORMAT_VERSION {
+		true => Ok(()),
+		false => Err(Error::InvalidFileData(format!(
+			"format version {version} is newer than the newest supported version {FORMAT_VERSION}"
+		))),
+	}
+}
+
+fn version_of(file: &Table) -> Result<u32, Error> {
+	let version = match file.get("format_version") {
+		None => 0,
+		Some(Value::Integer(version)) => u32::try_from(*version)
+			.map_err(|_| Error::InvalidFileData(format!("invalid format version {version}")))?,
+		Some(_) => {
+			let message = "format version isn't a number".to_string();
+			return Err(Error::InvalidFileData(message));
+		}
+	};
+	check_version(version)?;
+	Ok(version)
+}
+
+/// Version 1 added mirrors, owners, member lists and signed revisions to the header
+fn add_header_fields(file: &mut Table) -> Result<(), Error> {
+	let Some(Value::Table(header)) = file.get_mut("header") else {
+		return Err(Error::InvalidFileData("file has no header".to_string()));
+	};
+	for (field, empty) in [
+		("mirrors", Value::Array(Vec::new())),
+		("owner", Value::String(String::new())),
+		("member_lists", Value::Array(Vec::new())),
+		("revisions", Value::Array(Vec::new())),
+	] {
+		header.entry(field).or_insert(empty);
+	}
+	Ok(())
+}
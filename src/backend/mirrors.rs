@@ -0,0 +1,100 @@
+//! Reading a thread along with the copies contributors publish under their own IPNS links
+//!
+//! See `docs/GitArk link.md` for why mirrors exist, messages are only ever added so the thread
+//! is the union of every copy.
+use crate::{custom_types::*, read, store::ForumStore};
+use futures::future::join_all;
+use std::collections::HashMap;
+
+/// A message and every link it was found at
+#[derive(Debug, PartialEq, Clone)]
+pub struct MirroredMessage {
+	pub verified: VerifiedMessage,
+	/// In the order they were read, so the thread's own link comes first if it had the message
+	pub sources: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct MirroredThread {
+	/// The header from the thread's own link, mirrors can't change it
+	pub header: Header,
+	pub messages: Vec<MirroredMessage>,
+	/// Mirrors that couldn't be read, these don't stop the rest of the thread being read
+	pub failed_mirrors: Vec<(String, Error)>,
+}
+
+/// Read the thread and every mirror listed in its header, merging messages with the same hash
+///
+/// Only an error reading `link` itself is returned, errors reading mirrors are put in
+/// `failed_mirrors` as they are often offline.
+pub async fn get_mirrored_messages(
+	store: &impl ForumStore,
+	link: &str,
+) -> Result<MirroredThread, Error> {
+	let repo = read::get_repo(store, link).await?;
+	let mirrors: Vec<&String> = repo.header.mirrors.iter().filter(|m| *m != link).collect();
+	let mirror_messages = join_all(
+		mirrors
+			.iter()
+			.map(|mirror| read::get_mirror_messages(store, mirror)),
+	)
+	.await;
+
+	let mut merged = Merged::default();
+	merged.add(link, repo.messages);
+	let mut failed_mirrors = Vec::new();
+	for (mirror, result) in mirrors.into_iter().zip(mirror_messages) {
+		match result {
+			Ok(messages) => merged.add(mirror, messages),
+			Err(e) => failed_mirrors.push((mirror.clone(), e)),
+		}
+	}
+	Ok(MirroredThread {
+		header: repo.header,
+		messages: merged.messages,
+		failed_mirrors,
+	})
+}
+
+#[derive(Default)]
+struct Merged {
+	messages: Vec<MirroredMessage>,
+	/// Where each message that could be decoded is in `messages`
+	by_hash: HashMap<[u8; 64], usize>,
+}
+
+impl Merged {
+	fn add(&mut self, source: &str, file_messages: Vec<FileMessage>) {
+		for verified in file_messages.into_iter().map(read::verify_message) {
+			let index = match &verified.message {
+				Some(message) => self.by_hash.get(&message.get_hash()).copied(),
+				// Without a hash the only way to match them is if they're identical
+				None => self
+					.messages
+					.iter()
+					.position(|m| m.verified.file_message == verified.file_message),
+			};
+			match index {
+				Some(index) => add_source(&mut self.messages[index].sources, source),
+				None => self.push(source, verified),
+			}
+		}
+	}
+
+	fn push(&mut self, source: &str, verified: VerifiedMessage) {
+		if let Some(message) = &verified.message {
+			self.by_hash.insert(message.get_hash(), self.messages.len());
+		}
+		self.messages.push(MirroredMessage {
+			verified,
+			sources: vec![source.to_string()],
+		});
+	}
+}
+
+/// A copy can have the same message twice, it only needs listing once
+fn add_source(sources: &mut Vec<String>, source: &str) {
+	if !sources.iter().any(|s| s == source) {
+		sources.push(source.to_string());
+	}
+}
@@ -1,83 +1,220 @@
-use crate::custom_types::*;
+use crate::{
+	chains,
+	custom_types::*,
+	encoding::{self, FileFormat},
+	header,
+	linked::{self, MessageCache},
+	store::ForumStore,
+};
 use base64::{engine::general_purpose, Engine};
 use ed25519_dalek::*;
-use futures::TryStreamExt;
-use ipfs_api_backend_hyper::{IpfsApi, IpfsClient};
-use sha2::{Digest, Sha512};
 
-pub fn get_messages(link: &str) -> Result<Vec<Message>, Error> {
-	Ok(get_repo(link)?
+/// Get the messages that could be decoded, use `get_verified_messages` to see the ones that couldn't
+pub async fn get_messages(store: &impl ForumStore, link: &str) -> Result<Vec<Message>, Error> {
+	Ok(get_verified_messages(store, link)
+		.await?
+		.into_iter()
+		.filter_map(|verified| verified.message)
+		.collect())
+}
+
+/// Get every message in the file along with whether it is valid and why not
+pub async fn get_verified_messages(
+	store: &impl ForumStore,
+	link: &str,
+) -> Result<Vec<VerifiedMessage>, Error> {
+	Ok(get_repo(store, link)
+		.await?
 		.messages
 		.into_iter()
-		.filter_map(vec_to_message)
+		.map(verify_message)
 		.collect())
 }
 
-pub fn get_repo(link: &str) -> Result<FullFile, Error> {
-	let file_slice = read_file(link)?;
-	if file_slice.is_empty() {
-		return Ok(FullFile::new());
+pub async fn get_repo(store: &impl ForumStore, link: &str) -> Result<FullFile, Error> {
+	get_repo_cached(store, link, &mut MessageCache::new()).await
+}
+
+/// The hash of the last message of each chain, which can be replied to without forking the thread
+///
+/// If messages are stored as their own objects only the newest of each chain is fetched, unless
+/// one of them isn't validly signed.
+pub async fn get_chain_ends(
+	store: &impl ForumStore,
+	link: &str,
+	cache: &mut MessageCache,
+) -> Result<Vec<[u8; 64]>, Error> {
+	let (repo, _) = get_repo_with_format(store, link).await?;
+	if let Some(heads) = &repo.heads {
+		let ends = linked::chain_ends(store, heads, cache)
+			.await
+			.map_err(|e| e.context(Operation::ReadThread, link))?;
+		if let Some(ends) = ends {
+			return Ok(ends);
+		}
 	}
-	toml::from_str(&file_slice).map_err(Error::TomlDeserialization)
-}
-
-pub fn read_file(ipns_link: &str) -> Result<String, Error> {
-	let client = IpfsClient::default();
-	let executor = tokio::runtime::Builder::new_current_thread()
-		.enable_all()
-		.build()
-		.map_err(Error::StdIo)?;
-
-	let ipfs_link_future = client.name_resolve(Some(ipns_link), true, false);
-	let ipfs_link = executor.block_on(ipfs_link_future).map_err(Error::IPFS)?;
-	let content_future = client
-		.cat(&ipfs_link.path)
-		.map_ok(|chunk| chunk.to_vec())
-		.try_concat();
-	let content = executor.block_on(content_future).map_err(Error::IPFS)?;
-	String::from_utf8(content).map_err(Error::FromUtf8)
-}
-
-fn vec_to_message(f: FileMessage) -> Option<Message> {
-	let prev_hash_bytes: [u8; 64] = decode_base64(&f.prev_hash)?;
-	let public_key_bytes: [u8; PUBLIC_KEY_LENGTH] = decode_base64(&f.public_key)?;
-	let signature_bytes: [u8; 64] = decode_base64(&f.signature)?;
-
-	let to_hash = {
-		let mut result = Vec::<u8>::new();
-		result.extend_from_slice(&prev_hash_bytes);
-		result.extend_from_slice(&public_key_bytes);
-		result.extend_from_slice(f.body.as_bytes());
-		result.extend_from_slice(&signature_bytes);
-		result
+	let repo = with_linked_messages(store, link, repo, cache).await?;
+	let messages: Vec<Message> = repo
+		.messages
+		.into_iter()
+		.filter_map(|file_message| verify_message(file_message).message)
+		.collect();
+	Ok(chains::get_chains(&messages)
+		.iter()
+		.map(|chain| {
+			chain
+				.last()
+				.expect("Chains always have at least one message")
+				.get_hash()
+		})
+		.collect())
+}
+
+/// Get only the thread's header, messages stored as their own objects aren't fetched
+pub async fn get_header(store: &impl ForumStore, link: &str) -> Result<Header, Error> {
+	let (repo, _) = get_repo_with_format(store, link).await?;
+	Ok(repo.header)
+}
+
+/// Like `get_repo`, but messages stored as their own objects are only fetched if they aren't
+/// already in the cache, so reading the thread again only fetches the ones posted since
+pub async fn get_repo_cached(
+	store: &impl ForumStore,
+	link: &str,
+	cache: &mut MessageCache,
+) -> Result<FullFile, Error> {
+	let (repo, _) = get_repo_with_format(store, link).await?;
+	with_linked_messages(store, link, repo, cache).await
+}
+
+/// Get the messages from a contributor's copy of a thread
+///
+/// The copy's header isn't checked, as only the header at the thread's own link is used and its
+/// revisions are signed for that link.
+pub async fn get_mirror_messages(
+	store: &impl ForumStore,
+	link: &str,
+) -> Result<Vec<FileMessage>, Error> {
+	let contents = read_raw_file(store, link).await?;
+	let repo = encoding::decode(&contents).map_err(|e| e.context(Operation::ParseThread, link))?;
+	let repo = with_linked_messages(store, link, repo, &mut MessageCache::new()).await?;
+	Ok(repo.messages)
+}
+
+async fn with_linked_messages(
+	store: &impl ForumStore,
+	link: &str,
+	mut repo: FullFile,
+	cache: &mut MessageCache,
+) -> Result<FullFile, Error> {
+	if let Some(heads) = &repo.heads {
+		let linked = linked::fetch_messages(store, heads, cache)
+			.await
+			.map_err(|e| e.context(Operation::ReadThread, link))?;
+		repo.messages.extend(linked);
+	}
+	Ok(repo)
+}
+
+/// Get the repo as it's stored along with its format, so it can be written back the same way
+///
+/// Messages stored as their own objects aren't fetched, only their heads are in the file.
+pub async fn get_repo_with_format(
+	store: &impl ForumStore,
+	link: &str,
+) -> Result<(FullFile, FileFormat), Error> {
+	let contents = read_raw_file(store, link).await?;
+	let repo = parse_repo(&contents, link).map_err(|e| e.context(Operation::ParseThread, link))?;
+	Ok((repo, encoding::detect_format(&contents)))
+}
+
+fn parse_repo(contents: &[u8], link: &str) -> Result<FullFile, Error> {
+	let mut repo = encoding::decode(contents)?;
+	header::apply_revisions(&mut repo.header, link)?;
+	Ok(repo)
+}
+
+pub async fn read_file(store: &impl ForumStore, ipns_link: &str) -> Result<String, Error> {
+	read_file_bytes(store, ipns_link)
+		.await
+		.map_err(|e| e.context(Operation::ReadThread, ipns_link))
+}
+
+/// Get the file as it's stored, which isn't text if it's in the binary format
+pub async fn read_raw_file(store: &impl ForumStore, ipns_link: &str) -> Result<Vec<u8>, Error> {
+	read_raw_bytes(store, ipns_link)
+		.await
+		.map_err(|e| e.context(Operation::ReadThread, ipns_link))
+}
+
+/// Get the file as it's stored, except messages stored as their own objects are fetched and put
+/// back in it so the file can be read without them
+pub async fn read_standalone_file(store: &impl ForumStore, link: &str) -> Result<Vec<u8>, Error> {
+	let contents = read_raw_file(store, link).await?;
+	let repo = encoding::decode(&contents).map_err(|e| e.context(Operation::ParseThread, link))?;
+	if repo.heads.is_none() {
+		return Ok(contents);
+	}
+	let mut repo = with_linked_messages(store, link, repo, &mut MessageCache::new()).await?;
+	repo.heads = None;
+	encoding::encode(&repo, encoding::detect_format(&contents))
+}
+
+pub(crate) async fn read_file_bytes(
+	store: &impl ForumStore,
+	ipns_link: &str,
+) -> Result<String, Error> {
+	Ok(String::from_utf8(read_raw_bytes(store, ipns_link).await?)?)
+}
+
+async fn read_raw_bytes(store: &impl ForumStore, ipns_link: &str) -> Result<Vec<u8>, Error> {
+	let ipfs_link = store.resolve(ipns_link).await?;
+	store.cat(&ipfs_link).await
+}
+
+pub fn verify_message(file_message: FileMessage) -> VerifiedMessage {
+	let (message, status) = match file_message_to_message(&file_message) {
+		Ok(message) if message.is_signed() => (Some(message), MessageStatus::Valid),
+		Ok(message) => (Some(message), MessageStatus::BadSignature),
+		Err(status) => (None, status),
 	};
+	VerifiedMessage {
+		file_message,
+		message,
+		status,
+	}
+}
+
+fn file_message_to_message(f: &FileMessage) -> Result<Message, MessageStatus> {
+	let prev_hash: [u8; 64] = decode_base64(&f.prev_hash, MessageField::PrevHash)?;
+	let public_key_bytes: [u8; PUBLIC_KEY_LENGTH] =
+		decode_base64(&f.public_key, MessageField::PublicKey)?;
+	let signature_bytes: [u8; 64] = decode_base64(&f.signature, MessageField::Signature)?;
 
-	let prev_hash: [u8; 64] = decode_base64(&f.prev_hash)?;
-	let public_key = PublicKey::from_bytes(&public_key_bytes).ok()?;
-	let body = f.body;
-	let signature = match Signature::from_bytes(&signature_bytes) {
-		Ok(i) => Some(i),
-		Err(_) => None,
-	}?;
-	let message = Message {
+	let public_key =
+		PublicKey::from_bytes(&public_key_bytes).map_err(|_| MessageStatus::InvalidPublicKey)?;
+	let body = f.body.clone();
+	let signature =
+		Signature::from_bytes(&signature_bytes).map_err(|_| MessageStatus::InvalidSignature)?;
+	Ok(Message {
 		prev_hash,
 		public_key,
 		body,
 		signature,
-	};
-
-	let hash: [u8; 64] = Sha512::digest(to_hash).into();
-	if message.get_hash() == hash {
-		Some(message)
-	} else {
-		None
-	}
+	})
 }
 
-fn decode_base64<const N: usize>(string: &str) -> Option<[u8; N]> {
+fn decode_base64<const N: usize>(
+	string: &str,
+	field: MessageField,
+) -> Result<[u8; N], MessageStatus> {
 	// I want to use `decode_slice` for performance but it was not working
 	// maybe have another go sometime
 	let base64 = general_purpose::STANDARD_NO_PAD;
-	let as_vec = base64.decode(string).ok()?;
-	as_vec.try_into().ok()
+	let as_vec = base64
+		.decode(string)
+		.map_err(|_| MessageStatus::BadBase64(field))?;
+	as_vec
+		.try_into()
+		.map_err(|_| MessageStatus::WrongLength(field))
 }
@@ -0,0 +1,129 @@
+use crate::custom_types::Error;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+/// Everything the forum needs from the place it is stored
+///
+/// This mirrors the parts of IPFS that we use: immutable content that is addressed by its hash
+/// and mutable names (IPNS) that point to a piece of content and are controlled by a key.
+pub trait ForumStore: Sync {
+	/// Get the content path that the name currently points to, `Error::NotFound` if it points nowhere
+	fn resolve(&self, name: &str) -> impl Future<Output = Result<String, Error>> + Send;
+	/// Get the contents at the content path
+	fn cat(&self, path: &str) -> impl Future<Output = Result<Vec<u8>, Error>> + Send;
+	/// Store the data and return the content path it can be retrieved from
+	fn add(&self, data: Vec<u8>) -> impl Future<Output = Result<String, Error>> + Send;
+	/// Point the name controlled by the key to the content path
+	fn publish(&self, key: &str, path: &str) -> impl Future<Output = Result<(), Error>> + Send;
+	/// Create a new key and return the name it controls
+	fn key_gen(&self, key: &str) -> impl Future<Output = Result<String, Error>> + Send;
+	fn key_rename(
+		&self,
+		key: &str,
+		new_key: &str,
+	) -> impl Future<Output = Result<(), Error>> + Send;
+	fn key_rm(&self, key: &str) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// A store that only lives as long as the program, mainly useful for tests
+#[derive(Default)]
+pub struct MemoryStore {
+	content: Mutex<HashMap<String, Vec<u8>>>,
+	/// Key name to the name it controls
+	keys: Mutex<HashMap<String, String>>,
+	/// Name to the content path it points to
+	names: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryStore {
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl ForumStore for MemoryStore {
+	async fn resolve(&self, name: &str) -> Result<String, Error> {
+		let names = self.names.lock().unwrap();
+		match names.get(strip_ipns_prefix(name)) {
+			Some(path) => Ok(path.clone()),
+			None => Err(Error::NotFound(name.to_string())),
+		}
+	}
+
+	async fn cat(&self, path: &str) -> Result<Vec<u8>, Error> {
+		let content = self.content.lock().unwrap();
+		match content.get(path) {
+			Some(data) => Ok(data.clone()),
+			None => Err(Error::NotFound(path.to_string())),
+		}
+	}
+
+	async fn add(&self, data: Vec<u8>) -> Result<String, Error> {
+		let path = content_path(&data);
+		self.content.lock().unwrap().insert(path.clone(), data);
+		Ok(path)
+	}
+
+	async fn publish(&self, key: &str, path: &str) -> Result<(), Error> {
+		let keys = self.keys.lock().unwrap();
+		let name = match keys.get(key) {
+			Some(name) => name.clone(),
+			None => return Err(Error::NotFound(key.to_string())),
+		};
+		self.names.lock().unwrap().insert(name, path.to_string());
+		Ok(())
+	}
+
+	async fn key_gen(&self, key: &str) -> Result<String, Error> {
+		let mut keys = self.keys.lock().unwrap();
+		if keys.contains_key(key) {
+			return Err(Error::AlreadyExists(key.to_string()));
+		}
+		let name = random_name();
+		keys.insert(key.to_string(), name.clone());
+		Ok(name)
+	}
+
+	async fn key_rename(&self, key: &str, new_key: &str) -> Result<(), Error> {
+		let mut keys = self.keys.lock().unwrap();
+		if keys.contains_key(new_key) {
+			return Err(Error::AlreadyExists(new_key.to_string()));
+		}
+		let name = match keys.remove(key) {
+			Some(name) => name,
+			None => return Err(Error::NotFound(key.to_string())),
+		};
+		keys.insert(new_key.to_string(), name);
+		Ok(())
+	}
+
+	async fn key_rm(&self, key: &str) -> Result<(), Error> {
+		match self.keys.lock().unwrap().remove(key) {
+			Some(_) => Ok(()),
+			None => Err(Error::NotFound(key.to_string())),
+		}
+	}
+}
+
+/// IPNS links can be passed with or without the "/ipns/" at the start
+pub fn strip_ipns_prefix(name: &str) -> &str {
+	name.strip_prefix("/ipns/").unwrap_or(name)
+}
+
+/// Get the content path the data would be stored at, found by hashing the data
+pub fn content_path(data: &[u8]) -> String {
+	let hash = Sha256::digest(data);
+	format!("/ipfs/{}", to_hex(&hash))
+}
+
+/// Generate a random name for a new key
+pub fn random_name() -> String {
+	let mut bytes = [0; 32];
+	OsRng.fill_bytes(&mut bytes);
+	to_hex(&bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
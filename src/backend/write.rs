@@ -1,28 +1,217 @@
-use crate::{custom_types::*, read};
-use ipfs_api_backend_hyper::{IpfsApi, IpfsClient, KeyType};
-use std::io::Cursor;
+use crate::{
+	custom_types::*,
+	encoding::{self, FileFormat},
+	header,
+	linked::{self, MessageCache},
+	members, migrate, read,
+	store::ForumStore,
+};
+use ed25519_dalek::Keypair;
 
 /// Append the messages to the GitArk repo at the link
-pub fn write_messages(link: &str, messages: Vec<Message>) -> Result<(), Error> {
+pub async fn write_messages(
+	store: &impl ForumStore,
+	link: &str,
+	messages: Vec<Message>,
+) -> Result<(), Error> {
+	write_messages_cached(store, link, messages, &mut MessageCache::new()).await
+}
+
+/// Like `write_messages`, but messages stored as their own objects that are fetched to find what a
+/// reply is to are kept in the cache, and the ones already in it aren't fetched again
+pub async fn write_messages_cached(
+	store: &impl ForumStore,
+	link: &str,
+	messages: Vec<Message>,
+	cache: &mut MessageCache,
+) -> Result<(), Error> {
 	let name = &ipns_link_to_key(link);
-	let write_data = get_write_data(link, messages)?;
-	let data_as_toml = toml::to_string(&write_data).map_err(Error::TomlSerialization)?;
-	upload_to_ipns(name, data_as_toml)?;
-	Ok(())
+	let (write_data, format) = get_write_data(store, link, messages, cache).await?;
+	upload_file(store, name, &write_data, format)
+		.await
+		.map_err(|e| e.context(Operation::WriteThread, link))
 }
 
-fn get_write_data(link: &str, messages: Vec<Message>) -> Result<FullFile, Error> {
+async fn upload_file(
+	store: &impl ForumStore,
+	key: &str,
+	file: &FullFile,
+	format: FileFormat,
+) -> Result<(), Error> {
+	let contents = encoding::encode(file, format)?;
+	upload_to_ipns(store, key, contents).await
+}
+
+/// The file with the messages appended, in the format the thread is already stored in
+///
+/// If the thread stores messages as their own objects they are uploaded here and only the heads
+/// in the file change.
+async fn get_write_data(
+	store: &impl ForumStore,
+	link: &str,
+	messages: Vec<Message>,
+	cache: &mut MessageCache,
+) -> Result<(FullFile, FileFormat), Error> {
 	// Read existing messages (see Decisions.md for explanation)
-	let existing_file = read::get_repo(link)?;
+	let (mut file, format) = read::get_repo_with_format(store, link).await?;
 
 	let mut new_messages = messages.into_iter().map(message_to_file_message).collect();
-	let mut messages = existing_file.messages;
-	messages.append(&mut new_messages);
+	match &mut file.heads {
+		Some(heads) => linked::append(store, heads, new_messages, format, cache)
+			.await
+			.map_err(|e| e.context(Operation::WriteThread, link))?,
+		None => file.messages.append(&mut new_messages),
+	}
+	Ok((file, format))
+}
 
-	Ok(FullFile {
-		header: existing_file.header,
-		messages,
-	})
+/// Add a revision to the header, signed by the owner or anyone if the thread has no owner yet
+///
+/// Whoever signs the first revision becomes the owner.
+pub async fn edit_header(
+	store: &impl ForumStore,
+	link: &str,
+	revision: HeaderRevision,
+) -> Result<(), Error> {
+	let (mut repo, format) = read::get_repo_with_format(store, link).await?;
+	let header = &mut repo.header;
+	if !revision.is_signed(link) || !header::can_edit(header, &revision.signed_by) {
+		let message = format!("{} isn't the owner of {link}", revision.signed_by);
+		return Err(Error::Unauthorized(message));
+	}
+	if revision.revision as usize != header.revisions.len() {
+		let message = format!("revision {} is out of date", revision.revision);
+		return Err(Error::InvalidFileData(message));
+	}
+	header.owner = revision.signed_by.clone();
+	header.name = revision.name.clone();
+	header.thread_number = revision.thread_number;
+	header.tags = revision.tags.clone();
+	header.mirrors = revision.mirrors.clone();
+	header.revisions.push(revision);
+	upload_file(store, &ipns_link_to_key(link), &repo, format)
+		.await
+		.map_err(|e| e.context(Operation::WriteThread, link))
+}
+
+/// Add a contributor's copy of the thread to the header, so readers also fetch messages from it
+pub async fn add_mirror(
+	store: &impl ForumStore,
+	link: &str,
+	mirror: &str,
+	keypair: &Keypair,
+) -> Result<(), Error> {
+	let mut header = read::get_header(store, link).await?;
+	if header.mirrors.iter().any(|existing| existing == mirror) {
+		return Ok(());
+	}
+	header.mirrors.push(mirror.to_string());
+	let revision = HeaderRevision::new_signed(&header, link, keypair);
+	edit_header(store, link, revision).await
+}
+
+/// Append a new version of the member list to the header
+///
+/// The thread needs an owner first, which is whoever signed its first header revision.
+pub async fn add_member_list(
+	store: &impl ForumStore,
+	link: &str,
+	member_list: MemberList,
+) -> Result<(), Error> {
+	let (mut repo, format) = read::get_repo_with_format(store, link).await?;
+	let header = &mut repo.header;
+	if header.owner.is_empty() {
+		let message = format!("{link} has no owner to sign its member list");
+		return Err(Error::Unauthorized(message));
+	}
+	let current = members::current_list(header, link);
+	let index = header.member_lists.len();
+	if !members::can_sign(&header.owner, current, &member_list.signed_by)
+		|| !member_list.is_signed(link, index)
+	{
+		let message = format!(
+			"{} isn't the owner or a moderator of {link}",
+			member_list.signed_by
+		);
+		return Err(Error::Unauthorized(message));
+	}
+	header.member_lists.push(member_list);
+	upload_file(store, &ipns_link_to_key(link), &repo, format)
+		.await
+		.map_err(|e| e.context(Operation::WriteThread, link))
+}
+
+/// Republish the thread in another format, nothing happens if it's already in that format
+///
+/// Messages stored as their own objects are left in the format they were written in, as either
+/// can be read.
+pub async fn convert_repo(
+	store: &impl ForumStore,
+	link: &str,
+	format: FileFormat,
+) -> Result<(), Error> {
+	let contents = read::read_raw_file(store, link).await?;
+	if !contents.is_empty() && encoding::detect_format(&contents) == format {
+		return Ok(());
+	}
+	let converted = encoding::convert(&contents, format)
+		.map_err(|e| e.context(Operation::ParseThread, link))?;
+	upload_to_ipns(store, &ipns_link_to_key(link), converted)
+		.await
+		.map_err(|e| e.context(Operation::WriteThread, link))
+}
+
+/// Move the thread's messages into their own objects, so posting only uploads the new message
+///
+/// New threads already store them this way, so this is for threads made by older versions.
+/// Nothing happens if they already are.
+pub async fn link_messages(store: &impl ForumStore, link: &str) -> Result<(), Error> {
+	let (mut repo, format) = read::get_repo_with_format(store, link).await?;
+	if repo.heads.is_some() {
+		return Ok(());
+	}
+	let messages = std::mem::take(&mut repo.messages);
+	let upload = async {
+		let mut heads = Vec::new();
+		linked::append(
+			store,
+			&mut heads,
+			messages,
+			format,
+			&mut MessageCache::new(),
+		)
+		.await?;
+		repo.heads = Some(heads);
+		upload_file(store, &ipns_link_to_key(link), &repo, format).await
+	};
+	upload
+		.await
+		.map_err(|e| e.context(Operation::WriteThread, link))
+}
+
+/// Republish the thread in the newest format version, returns the version it was in before
+///
+/// Nothing happens if it is already in the newest version. Unlike writing messages this keeps the
+/// header as it is stored, rather than as it is after applying revisions.
+pub async fn migrate_repo(store: &impl ForumStore, link: &str) -> Result<u32, Error> {
+	let contents = read::read_raw_file(store, link).await?;
+	let (version, repo) =
+		upgrade(&contents).map_err(|e| e.context(Operation::ParseThread, link))?;
+	if version == migrate::FORMAT_VERSION {
+		return Ok(version);
+	}
+	let format = encoding::detect_format(&contents);
+	upload_file(store, &ipns_link_to_key(link), &repo, format)
+		.await
+		.map_err(|e| e.context(Operation::WriteThread, link))?;
+	Ok(version)
+}
+
+fn upgrade(contents: &[u8]) -> Result<(u32, FullFile), Error> {
+	Ok((
+		encoding::stored_version(contents)?,
+		encoding::decode(contents)?,
+	))
 }
 
 pub fn message_to_file_message(m: Message) -> FileMessage {
@@ -38,69 +227,90 @@ pub fn message_to_file_message(m: Message) -> FileMessage {
 	}
 }
 
-fn upload_to_ipns(key: &str, contents: String) -> Result<(), Error> {
-	let client = IpfsClient::default();
-	let data = Cursor::new(contents);
-	let executor = tokio::runtime::Builder::new_current_thread()
-		.enable_all()
-		.build()
-		.map_err(Error::StdIo)?;
-
-	let result_future = client.add(data);
-	let result = executor.block_on(result_future).map_err(Error::IPFS)?;
-	let ipfs_link = format!("/ipfs/{}", result.name);
+pub(crate) async fn upload_to_ipns(
+	store: &impl ForumStore,
+	key: &str,
+	contents: Vec<u8>,
+) -> Result<(), Error> {
+	let ipfs_link = store.add(contents).await?;
+	store.publish(key, &ipfs_link).await
+}
 
-	let publish_future = client.name_publish(&ipfs_link, false, None, None, Some(key));
-	executor.block_on(publish_future).map_err(Error::IPFS)?;
-	Ok(())
+/// Creates a new IPNS link with the header and no messages, returns the link to it
+///
+/// The thread has no owner until someone signs a header revision, use `new_owned_ipns` to sign
+/// the first one when creating it.
+pub async fn new_ipns(store: &impl ForumStore, header: Header) -> Result<String, Error> {
+	create_thread(store, header, None).await
 }
 
-/// Creates a new IPNS link and returns the link to it
-pub fn new_ipns() -> Result<String, Error> {
-	let client = IpfsClient::default();
-	let executor = tokio::runtime::Builder::new_current_thread()
-		.enable_all()
-		.build()
-		.map_err(Error::StdIo)?;
+/// Like `new_ipns`, but the header is signed by the keypair as its first revision, which makes
+/// them the owner
+pub async fn new_owned_ipns(
+	store: &impl ForumStore,
+	header: Header,
+	keypair: &Keypair,
+) -> Result<String, Error> {
+	create_thread(store, header, Some(keypair)).await
+}
 
+async fn create_thread(
+	store: &impl ForumStore,
+	header: Header,
+	owner: Option<&Keypair>,
+) -> Result<String, Error> {
 	let temp_key = "ThisIsATempGitArkNameIfYouAreSeeingThisSomethingWentWrong";
-	let keygen_future = client.key_gen(temp_key, KeyType::Ed25519, 64);
-	let result = executor.block_on(keygen_future).map_err(Error::IPFS)?;
-	let ipns_link = result.id;
-
-	write_to_new_ipns(temp_key)?;
-	rename_ipns_key(temp_key, &ipns_link)?;
-
-	Ok(ipns_link)
+	let name = format!("\"{}\"", header.name);
+	publish_thread(store, temp_key, header, owner)
+		.await
+		.map_err(|e| e.context(Operation::CreateThread, name))
 }
 
-fn write_to_new_ipns(key: &str) -> Result<(), Error> {
-	let data_as_toml = toml::to_string(&FullFile::new()).map_err(Error::TomlSerialization)?;
-	upload_to_ipns(key, data_as_toml)
+async fn publish_thread(
+	store: &impl ForumStore,
+	temp_key: &str,
+	mut header: Header,
+	owner: Option<&Keypair>,
+) -> Result<String, Error> {
+	create_ipns(store, temp_key, |link| {
+		if let Some(keypair) = owner {
+			let revision = HeaderRevision::new_signed(&header, link, keypair);
+			header.owner = revision.signed_by.clone();
+			header.revisions.push(revision);
+		}
+		// Messages are stored as their own objects so posting doesn't upload the whole thread
+		FullFile {
+			header,
+			heads: Some(Vec::new()),
+			..FullFile::new()
+		}
+	})
+	.await
 }
 
-fn rename_ipns_key(key_name: &str, link: &str) -> Result<(), Error> {
-	let client = IpfsClient::default();
-	let executor = tokio::runtime::Builder::new_current_thread()
-		.enable_all()
-		.build()
-		.map_err(Error::StdIo)?;
+/// Creates a new IPNS link pointing to the file as TOML, returns the link to it
+///
+/// The file is made once the link is known so it can sign it, the key is renamed to the link after.
+pub(crate) async fn create_ipns<T: serde::Serialize>(
+	store: &impl ForumStore,
+	temp_key: &str,
+	make_file: impl FnOnce(&str) -> T,
+) -> Result<String, Error> {
+	let ipns_link = store.key_gen(temp_key).await?;
+	let file = make_file(&ipns_link);
+	upload_to_ipns(store, temp_key, toml::to_string(&file)?.into_bytes()).await?;
+	rename_ipns_key(store, temp_key, &ipns_link).await?;
+	Ok(ipns_link)
+}
 
+async fn rename_ipns_key(store: &impl ForumStore, key_name: &str, link: &str) -> Result<(), Error> {
 	let new_name = ipns_link_to_key(link);
-	let key_rename_future = client.key_rename(key_name, &new_name, false);
-	executor.block_on(key_rename_future).map_err(Error::IPFS)?;
-	Ok(())
+	store.key_rename(key_name, &new_name).await
 }
 
-pub fn delete_key(key: &str) -> Result<(), Error> {
-	let client = IpfsClient::default();
-	let executor = tokio::runtime::Builder::new_current_thread()
-		.enable_all()
-		.build()
-		.map_err(Error::StdIo)?;
-
-	let command = client.key_rm(key);
-	executor.block_on(command).map_err(Error::IPFS)?;
-
-	Ok(())
+pub async fn delete_key(store: &impl ForumStore, key: &str) -> Result<(), Error> {
+	store
+		.key_rm(key)
+		.await
+		.map_err(|e| e.context(Operation::DeleteKey, key))
 }
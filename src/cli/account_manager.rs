@@ -1,14 +1,16 @@
-use crate::{
-	encrypt_decrypt::{encrypt_and_write, read_and_decrypt},
-	input::*,
-	throw, Error,
-};
+use crate::{arguments::SignerArgs, error, errors::*, input::*, throw, Error};
+use crypto_forum::account_file::*;
 use ed25519_dalek::*;
-use sha2::{Digest, Sha256, Sha512};
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha512};
 use std::fs;
+use zeroize::Zeroize;
 
-pub fn login(accounts_dir: &str) -> Keypair {
-	create_dir(accounts_dir);
+/// How many times the user can enter the wrong password before giving up
+const MAX_PASSWORD_ATTEMPTS: u32 = 3;
+
+pub fn login(accounts_dir: &str) -> Result<Keypair, CliError> {
+	create_dir(accounts_dir)?;
 	if dir_is_empty(accounts_dir) {
 		return create_account(accounts_dir);
 	}
@@ -23,50 +25,70 @@ fn dir_is_empty(directory: &str) -> bool {
 	}
 }
 
-fn create_account(accounts_dir: &str) -> Keypair {
+fn create_account(accounts_dir: &str) -> Result<Keypair, CliError> {
 	let account_name = input("Enter new account name:");
 
 	let first_password = get_password("Please create a password");
 	let second_password = get_password("Please repeat that password");
 	if first_password != second_password {
-		println!("Passwords do not match.");
+		eprintln!("Passwords do not match.");
 		return create_account(accounts_dir);
 	}
 
 	let file_path = [accounts_dir, &account_name].concat();
-	let keypair = new_keypair();
-	let result = encrypt_and_write(&file_path, &keypair.to_bytes(), &first_password);
-	check_encrypt_and_write_error(result);
-	keypair
+	let keypair = new_keypair()?;
+	let params = KdfParams::default();
+	let mut keypair_bytes = keypair.to_bytes();
+	let result = write_account(&file_path, &keypair_bytes, &first_password, &params);
+	keypair_bytes.zeroize();
+	result.map_err(encrypt_and_write_error)?;
+	Ok(keypair)
 }
 
-fn check_encrypt_and_write_error(result: Result<(), Error>) {
-	let err = match result {
-		Ok(()) => return,
-		Err(e) => e,
-	};
-	match err {
-		Error::Encryption(e) => throw!("Error encrypting password: {e}"),
-		Error::StdIo(e) => throw!("Error writing to account file: {e}"),
-		e => throw!("Unexpected error while encrypting and saving the account: {e}"),
+fn encrypt_and_write_error(err: Error) -> CliError {
+	let message = match err.root() {
+		Error::Encryption(e) => format!("Error encrypting password: {e}"),
+		Error::KeyDerivation(e) => format!("Error deriving key from password: {e}"),
+		Error::StdIo(e) => format!("Error writing to account file: {e}"),
+		_ => format!(
+			"Unexpected error while encrypting and saving the account: {}",
+			full_message(&err)
+		),
 	};
+	CliError::new(ErrorKind::of(&err), message)
 }
 
-fn create_dir(accounts_dir: &str) {
+fn create_dir(accounts_dir: &str) -> Result<(), CliError> {
 	let result = fs::create_dir(accounts_dir);
 	let err = match result {
-		Ok(()) => return,
+		Ok(()) => return Ok(()),
 		Err(err) => err,
 	};
 	if err.kind() == std::io::ErrorKind::AlreadyExists {
-		return;
+		return Ok(());
 	}
-	throw!("Error creating directory: {err}");
+	throw!(Io, "Error creating directory: {err}");
 }
 
-fn get_existing_account(accounts_dir: &str) -> Keypair {
-	println!("Accounts:");
-	let account_files = get_and_print_accounts(accounts_dir);
+pub fn list_accounts(accounts_dir: &str) -> Result<(), CliError> {
+	create_dir(accounts_dir)?;
+	for account in get_accounts(accounts_dir)? {
+		println!("{account}");
+	}
+	Ok(())
+}
+
+pub fn new_account(accounts_dir: &str) -> Result<Keypair, CliError> {
+	create_dir(accounts_dir)?;
+	create_account(accounts_dir)
+}
+
+fn get_existing_account(accounts_dir: &str) -> Result<Keypair, CliError> {
+	eprintln!("Accounts:");
+	let account_files = get_accounts(accounts_dir)?;
+	for account in &account_files {
+		eprintln!("{account}");
+	}
 
 	let prompt = "What account would you like to use? (type \"new\" to create a new one)";
 	let selection = input(prompt);
@@ -76,72 +98,166 @@ fn get_existing_account(accounts_dir: &str) -> Keypair {
 	if &selection == "new" {
 		return create_account(accounts_dir);
 	}
-	println!("Invalid selection, please pick an account");
+	eprintln!("Invalid selection, please pick an account");
 	get_existing_account(accounts_dir)
 }
 
-fn open_account(selection: &str, accounts_dir: &str) -> Keypair {
-	let password = get_password(&format!("Please enter the password for {selection}"));
-	let full_path = accounts_dir.to_owned() + &selection;
-	let file_data =
-		read_and_decrypt(&full_path, &password).unwrap_or_else(handle_read_and_decrypt_error);
-	Keypair::from_bytes(&file_data).unwrap_or_else(handle_key_creation_error)
+fn open_account(selection: &str, accounts_dir: &str) -> Result<Keypair, CliError> {
+	let full_path = accounts_dir.to_owned() + selection;
+	let prompt = format!("Please enter the password for {selection}");
+	let mut attempts_left = MAX_PASSWORD_ATTEMPTS;
+	loop {
+		let password = get_password(&prompt);
+		attempts_left -= 1;
+		match read_account(&full_path, &password) {
+			Ok(account) => return keypair_from_account(&full_path, account, &password),
+			Err(e) if matches!(e.root(), Error::WrongPassword) && attempts_left > 0 => {
+				eprintln!("Wrong password, {attempts_left} attempt(s) left");
+			}
+			Err(e) if matches!(e.root(), Error::WrongPassword) => throw!(
+				UserInput,
+				"Wrong password or corrupted account file, too many failed attempts"
+			),
+			Err(e) => return Err(read_and_decrypt_error(e)),
+		}
+	}
 }
 
-fn handle_read_and_decrypt_error(err: Error) -> Vec<u8> {
-	match err {
-		Error::StdIo(e) => throw!("Error reading from account file: {e}"),
-		Error::Encryption(e) => throw!("Error decrypting password: {e}"), //TODO ask again after wrong password
-		e => throw!("Unexpected error while encrypting and saving the account: {e}"),
-	}
+/// Open the account without asking the user anything, for use in scripts
+pub fn open_signing_account(signer: &SignerArgs, accounts_dir: &str) -> Result<Keypair, CliError> {
+	let password = get_password_without_asking(signer.password_fd)?;
+	let full_path = accounts_dir.to_owned() + &signer.account;
+	let account = read_account(&full_path, &password).map_err(read_and_decrypt_error)?;
+	keypair_from_account(&full_path, account, &password)
 }
 
-fn handle_key_creation_error(err: SignatureError) -> Keypair {
-	throw!("Error creating key from account file: {err}")
+/// The password is taken from --password-fd, then the CRYPTO_FORUM_PASSWORD environment variable
+fn get_password_without_asking(password_fd: Option<u32>) -> Result<String, CliError> {
+	let password = match password_fd {
+		Some(fd) => read_password_fd(fd)?,
+		None => match std::env::var("CRYPTO_FORUM_PASSWORD") {
+			Ok(password) => password,
+			Err(_) => throw!(
+				UserInput,
+				"A password is needed, use --password-fd=<FD> or CRYPTO_FORUM_PASSWORD"
+			),
+		},
+	};
+	// Only the newline `echo` and files end with is removed, spaces may be part of the password
+	let password = password.strip_suffix('\n').unwrap_or(&password);
+	let password = password.strip_suffix('\r').unwrap_or(password);
+	Ok(password.to_string())
 }
 
-fn get_and_print_accounts(accounts_dir: &str) -> Vec<String> {
-	let files = get_account_files(accounts_dir);
-	files.filter_map(get_and_print_str).collect()
+#[cfg(unix)]
+fn read_password_fd(fd: u32) -> Result<String, CliError> {
+	fs::read_to_string(format!("/dev/fd/{fd}"))
+		.map_err(|e| error!(Io, "Error reading password from file descriptor {fd}: {e}"))
 }
 
-fn get_account_files(accounts_dir: &str) -> fs::ReadDir {
-	match fs::read_dir(accounts_dir) {
-		Ok(res) => res,
-		Err(e) => throw!("Failed to retrieve accounts from {accounts_dir}: {e}"),
+#[cfg(not(unix))]
+fn read_password_fd(_fd: u32) -> Result<String, CliError> {
+	throw!(
+		UserInput,
+		"--password-fd is only supported on Unix, use CRYPTO_FORUM_PASSWORD instead"
+	)
+}
+
+fn keypair_from_account(
+	full_path: &str,
+	account: AccountData,
+	password: &str,
+) -> Result<Keypair, CliError> {
+	let keypair = Keypair::from_bytes(&account.data).map_err(key_creation_error)?;
+	if account.needs_migration {
+		migrate_account(full_path, &keypair, password);
+	}
+	Ok(keypair)
+}
+
+/// Rewrite an account file that's in an old format, now that we know the password is correct
+fn migrate_account(full_path: &str, keypair: &Keypair, password: &str) {
+	let params = KdfParams::default();
+	let mut keypair_bytes = keypair.to_bytes();
+	let result = write_account(full_path, &keypair_bytes, password, &params);
+	keypair_bytes.zeroize();
+	match result {
+		Ok(()) => eprintln!("Upgraded {full_path} to the latest account file format"),
+		// The old file is still usable so carry on and try again next time
+		Err(e) => eprintln!(
+			"Could not upgrade {full_path} to the latest account file format: {}",
+			full_message(&e)
+		),
 	}
 }
 
-fn get_and_print_str(input: Result<fs::DirEntry, std::io::Error>) -> Option<String> {
+fn read_and_decrypt_error(err: Error) -> CliError {
+	let message = match err.root() {
+		Error::StdIo(e) => format!("Error reading from account file: {e}"),
+		Error::KeyDerivation(e) => format!("Error deriving key from password: {e}"),
+		Error::WrongPassword => "Wrong password".to_string(),
+		Error::InvalidFileData(e) => format!("Account file is corrupt or truncated: {e}"),
+		Error::Encryption(e) => format!("Error decrypting password: {e}"),
+		_ => format!(
+			"Unexpected error while reading the account: {}",
+			full_message(&err)
+		),
+	};
+	CliError::new(ErrorKind::of(&err), message)
+}
+
+fn key_creation_error(err: SignatureError) -> CliError {
+	error!(
+		Parse,
+		"Account file is corrupt, error creating key from it: {err}"
+	)
+}
+
+fn get_accounts(accounts_dir: &str) -> Result<Vec<String>, CliError> {
+	let files = fs::read_dir(accounts_dir)
+		.map_err(|e| error!(Io, "Failed to retrieve accounts from {accounts_dir}: {e}"))?;
+	Ok(files.filter_map(get_account_name).collect())
+}
+
+fn get_account_name(input: Result<fs::DirEntry, std::io::Error>) -> Option<String> {
 	let file = input.ok()?;
 	if !file.path().is_file() {
 		return None;
 	}
-	let file_name = file.file_name().to_str()?.to_owned();
-	println!("{file_name}");
-	Some(file_name)
+	Some(file.file_name().to_str()?.to_owned())
 }
 
-fn new_keypair() -> Keypair {
-	let secret_seed = get_random_from_usr();
+fn new_keypair() -> Result<Keypair, CliError> {
+	let mut secret_seed = [0; SECRET_KEY_LENGTH];
+	OsRng.fill_bytes(&mut secret_seed);
+	let prompt = "Would you like to type some extra randomness to mix into the key? (not required)";
+	if ask_for_bool(prompt) {
+		mix_in_random_from_usr(&mut secret_seed);
+	}
 
-	let secret: SecretKey = SecretKey::from_bytes(&secret_seed[..SECRET_KEY_LENGTH])
-		.unwrap_or_else(|e| throw!("Unexpected error when creating key: {e}"));
+	let secret = SecretKey::from_bytes(&secret_seed);
+	secret_seed.zeroize();
+	let secret = secret.map_err(|e| error!(Crypto, "Unexpected error when creating key: {e}"))?;
 	let public: PublicKey = PublicKey::from(&secret);
-	Keypair { secret, public }
+	Ok(Keypair { secret, public })
 }
 
-/// Get the user to enter some random characters, then hash whatever they give and return that hash as a byte array
-fn get_random_from_usr() -> [u8; 64] {
-	let random_input = input(
-		"Please type some random characters (this will be used for the initial key generation)",
-	);
-	let hash = Sha512::digest(random_input);
-	hash.into()
+/// Get the user to enter some random characters, then hash them together with the seed
+///
+/// Because the seed is hashed in as well the result is never less random than the seed,
+/// no matter what the user types
+fn mix_in_random_from_usr(seed: &mut [u8; SECRET_KEY_LENGTH]) {
+	let mut random_input = input("Please type some random characters");
+	let mut hash: [u8; 64] = Sha512::new()
+		.chain_update(&seed)
+		.chain_update(&random_input)
+		.finalize()
+		.into();
+	seed.copy_from_slice(&hash[..SECRET_KEY_LENGTH]);
+	hash.zeroize();
+	random_input.zeroize();
 }
 
-fn get_password(prompt: &str) -> [u8; 32] {
-	let user_input = input(prompt);
-	let hash = Sha256::digest(user_input);
-	hash.into()
+fn get_password(prompt: &str) -> String {
+	input(prompt)
 }
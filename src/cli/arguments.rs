@@ -1,99 +1,485 @@
-#[derive(PartialEq)]
-pub enum Argument {
-	Interactive,
-	MachineOutput,
-	Create,
-}
-
-pub fn get_args() -> (Vec<String>, Vec<Argument>) {
-	// Skip the first argument because it's just the executable path
-	let arguments_as_strings: Vec<String> = std::env::args().skip(1).collect();
-
-	if arguments_as_strings.is_empty() {
-		println!(
-			"{program_name}: missing operand",
-			program_name = env!("CARGO_PKG_NAME")
-		);
-		std::process::exit(1)
-	}
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use crypto_forum::{encoding::FileFormat, ipfs_store::IpfsConfig};
+use std::time::Duration;
+
+const LONG_VERSION: &str = concat!(
+	env!("CARGO_PKG_VERSION"),
+	"
+
+Copyright (C) 2022 Alexander Gorichev
+License GPL-3.0-only: GNU GPL version 3.0 only <https://gnu.org/licenses/gpl-3.0.html>.
+This is free software: you are free to change and redistribute it.
+There is NO WARRANTY, to the extent permitted by law.
+
+Written by Alexander Gorichev.
+Send all issues to <https://github.com/Voklen/Crypto-Forum/issues> or <Alex.Gorichev@protonmail.com>
+I hope you enjoy :)"
+);
+
+const AFTER_HELP: &str = "To create a new repo run:
+	crypto_forum create
+To edit an existing repo use:
+	crypto_forum read -i <IPNS link>
+
+Send all issues to <https://github.com/Voklen/Crypto-Forum/issues> or <Alex.Gorichev@protonmail.com>";
+
+/// Distributed git service
+#[derive(Parser)]
+#[command(
+	version,
+	long_version = LONG_VERSION,
+	after_help = AFTER_HELP,
+	disable_version_flag = true
+)]
+pub struct Cli {
+	// `-v` was the version flag before subcommands, so it's kept for anyone still using it
+	/// Print version
+	#[arg(short = 'V', long, short_alias = 'v', action = ArgAction::Version)]
+	version: Option<bool>,
+	#[command(flatten)]
+	pub store: StoreArgs,
+	#[command(subcommand)]
+	pub command: Command,
+}
+
+/// Where the forum is stored, these can be given to every command
+#[derive(Args)]
+pub struct StoreArgs {
+	/// Use the directory as the store instead of IPFS
+	#[arg(long, global = true, value_name = "DIR")]
+	pub local: Option<String>,
+	/// Address of the IPFS API
+	#[arg(
+		long,
+		global = true,
+		env = "CRYPTO_FORUM_API",
+		value_name = "URL",
+		default_value = "http://localhost:5001"
+	)]
+	pub api: String,
+	/// Seconds before an IPFS request gives up, 0 to wait forever
+	#[arg(
+		long,
+		global = true,
+		env = "CRYPTO_FORUM_TIMEOUT",
+		value_name = "SECS",
+		default_value_t = 60
+	)]
+	pub timeout: u64,
+	/// Retry failed IPFS requests this many times
+	#[arg(
+		long,
+		global = true,
+		env = "CRYPTO_FORUM_RETRIES",
+		value_name = "N",
+		default_value_t = 0
+	)]
+	pub retries: u32,
+	/// Username and password for the IPFS API
+	#[arg(
+		long,
+		global = true,
+		env = "CRYPTO_FORUM_AUTH",
+		value_name = "USER:PASS",
+		value_parser = parse_auth,
+		hide_env_values = true
+	)]
+	pub auth: Option<(String, String)>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+	/// Print the messages in one or more threads
+	Read(ReadArgs),
+	/// Post one message without prompting
+	Post(PostArgs),
+	/// Create a new repo, asking for anything in the header that isn't given
+	Create(CreateArgs),
+	/// Manage the accounts on this computer
+	#[command(subcommand)]
+	Account(AccountCommand),
+	/// Check every message is valid and linked, exits with 1 if not
+	Verify(LinksArgs),
+	/// Print the raw thread file
+	Export(ExportArgs),
+	/// Republish a thread in another file format or with its messages stored separately
+	Convert(ConvertArgs),
+	/// Republish threads written by older versions in the newest format version
+	Migrate(MigrateArgs),
+	/// Add a contributor's copy of a thread to the thread's list of mirrors
+	Mirror(MirrorArgs),
+	/// See or change who is allowed to post in a thread
+	#[command(subcommand)]
+	Members(MembersCommand),
+	/// See or change the name, number and tags of a thread
+	#[command(subcommand)]
+	Header(HeaderCommand),
+	/// Group threads into a forum and open them by number
+	#[command(subcommand)]
+	Forum(ForumCommand),
+}
+
+/// How to display a thread
+#[derive(Args)]
+pub struct DisplayArgs {
+	/// Run in interactive mode
+	#[arg(short, long)]
+	pub interactive: bool,
+	/// Print in a way more sutible for parsing, the same as --format=machine
+	#[arg(short, long, conflicts_with = "format")]
+	pub machine_output: bool,
+	/// How to print the messages
+	#[arg(long, value_enum, default_value_t = OutputFormat::Human)]
+	pub format: OutputFormat,
+	/// Show every reply instead of collapsing long subthreads
+	#[arg(short, long)]
+	pub expand: bool,
+	/// Also read the copies of the thread listed in its header
+	#[arg(long)]
+	pub mirrors: bool,
+	/// What to do with messages from keys that aren't in the thread's member list
+	#[arg(long, value_enum, default_value_t = NonMembers::Flag)]
+	pub non_members: NonMembers,
+}
 
-	let mut links = Vec::<String>::new();
-	let mut arguments = Vec::<Argument>::new();
-	for arg in arguments_as_strings {
-		if arg.starts_with('-') {
-			arguments.push(parse_dashes(&arg))
-		} else {
-			// If there is no "-" at the start of the argument, it's a link that's being passed
-			links.push(arg);
+impl DisplayArgs {
+	pub fn output_format(&self) -> OutputFormat {
+		match self.machine_output {
+			true => OutputFormat::Machine,
+			false => self.format,
 		}
 	}
-	(links, arguments)
 }
 
-fn parse_dashes(arg: &str) -> Argument {
-	match arg {
-		"-i" => Argument::Interactive,
-		"-m" => Argument::MachineOutput,
-		"-c" => Argument::Create,
-		"-v" => print_version_info(),
-		"-h" => print_help(),
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+	/// A tree of the conversation
+	Human,
+	/// One line per message, with the body last
+	Machine,
+	/// A JSON array with an object per thread
+	Json,
+	/// One JSON object per message, per line
+	Ndjson,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum NonMembers {
+	/// Show them like any other message
+	Show,
+	/// Show them with a warning
+	Flag,
+	/// Leave them out
+	Hide,
+}
 
-		"--interactive" => Argument::Interactive,
-		"--machine-output" => Argument::MachineOutput,
-		"--create" => Argument::Create,
-		"--version" => print_version_info(),
-		"--help" => print_help(),
+#[derive(Args)]
+pub struct ReadArgs {
+	#[arg(required = true, value_name = "LINK")]
+	pub links: Vec<String>,
+	#[command(flatten)]
+	pub display: DisplayArgs,
+}
+
+#[derive(Args)]
+pub struct CreateArgs {
+	/// Name of the thread
+	#[arg(long)]
+	pub name: Option<String>,
+	#[arg(long, value_name = "NUMBER")]
+	pub thread_number: Option<u32>,
+	/// Tag to add to the thread, can be given more than once
+	#[arg(long, value_name = "TAG")]
+	pub tag: Vec<String>,
+	/// Create the thread without tags instead of asking for them
+	#[arg(long, conflicts_with = "tag")]
+	pub no_tags: bool,
+	/// Account to sign the header with, which makes it the thread's owner (default: ask)
+	#[arg(long, value_name = "NAME")]
+	pub account: Option<String>,
+	/// Read the account password from this file descriptor, only on Unix
+	/// (default: the CRYPTO_FORUM_PASSWORD environment variable)
+	#[arg(long, value_name = "FD", requires = "account")]
+	pub password_fd: Option<u32>,
+	#[command(flatten)]
+	pub display: DisplayArgs,
+}
 
-		_ => unknown_arg(arg),
+#[derive(Args)]
+pub struct PostArgs {
+	pub link: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
+	/// Message to post (default: read from --file or stdin)
+	#[arg(long, value_name = "TEXT", conflicts_with = "file")]
+	pub message: Option<String>,
+	/// File containing the message to post
+	#[arg(long, value_name = "PATH")]
+	pub file: Option<String>,
+	/// Hash of the message to reply to (default: the end of the only chain)
+	#[arg(long, value_name = "HASH")]
+	pub reply_to: Option<String>,
+}
+
+/// The account to sign with, the password is never asked for so this can be used from scripts
+#[derive(Args)]
+pub struct SignerArgs {
+	/// Account to sign with
+	#[arg(long, value_name = "NAME")]
+	pub account: String,
+	/// Read the account password from this file descriptor, only on Unix
+	/// (default: the CRYPTO_FORUM_PASSWORD environment variable)
+	#[arg(long, value_name = "FD")]
+	pub password_fd: Option<u32>,
+}
+
+#[derive(Subcommand)]
+pub enum AccountCommand {
+	/// List the accounts
+	List,
+	/// Create a new account
+	New,
+}
+
+#[derive(Args)]
+pub struct LinksArgs {
+	#[arg(required = true, value_name = "LINK")]
+	pub links: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+	pub link: String,
+	/// Write to this file instead of stdout
+	#[arg(short, long, value_name = "PATH")]
+	pub output: Option<String>,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("change").required(true).multiple(true).args(["to", "linked"])))]
+pub struct ConvertArgs {
+	pub link: String,
+	/// Format to republish the thread in
+	#[arg(long, value_enum)]
+	pub to: Option<StoredFormat>,
+	/// Store every message as its own object, so posting doesn't upload the whole thread again
+	/// (new threads already do)
+	#[arg(long)]
+	pub linked: bool,
+}
+
+#[derive(Args)]
+pub struct MigrateArgs {
+	#[arg(required = true, value_name = "LINK")]
+	pub links: Vec<String>,
+	/// The links are forums, migrate every thread listed in them
+	#[arg(long)]
+	pub forum: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum StoredFormat {
+	/// Text that can be read and edited by hand
+	Toml,
+	/// Smaller and quicker to load, for large threads
+	Binary,
+}
+
+impl StoredFormat {
+	pub fn file_format(self) -> FileFormat {
+		match self {
+			Self::Toml => FileFormat::Toml,
+			Self::Binary => FileFormat::Binary,
+		}
 	}
 }
 
-fn unknown_arg(arg: &str) -> ! {
-	let program_name = env!("CARGO_PKG_NAME");
-	println!("{program_name}: invalid option -- '{arg}'",);
-	std::process::exit(1)
+#[derive(Args)]
+pub struct MirrorArgs {
+	pub link: String,
+	/// IPNS link to the contributor's copy
+	pub mirror: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
 }
 
-fn print_version_info() -> ! {
-	println!(
-		"{program_name} {program_ver}",
-		program_name = env!("CARGO_PKG_NAME"),
-		program_ver = env!("CARGO_PKG_VERSION")
-	);
-	println!(
-		"
-Copyright (C) 2022 Alexander Gorichev
-License GPL-3.0-only: GNU GPL version 3.0 only <https://gnu.org/licenses/gpl-3.0.html>.
-This is free software: you are free to change and redistribute it.
-There is NO WARRANTY, to the extent permitted by law.
+#[derive(Subcommand)]
+pub enum MembersCommand {
+	/// Print the owner, moderators and members of a thread
+	Show(LinkArgs),
+	/// Sign a new member list, as the owner or a moderator
+	Edit(EditMembersArgs),
+}
 
-Written by Alexander Gorichev.
-Send all issues to <https://github.com/Voklen/Crypto-Forum/issues> or <Alex.Gorichev@protonmail.com>
-I hope you enjoy :)"
-	);
-	std::process::exit(0)
+#[derive(Args)]
+pub struct LinkArgs {
+	pub link: String,
 }
 
-fn print_help() -> ! {
-	let program_name = env!("CARGO_PKG_NAME");
-	println!(
-		"
-Usage: {program_name} [OPTION]...
-Distributed git service
+#[derive(Args)]
+pub struct EditMembersArgs {
+	pub link: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
+	/// Public key to allow to post
+	#[arg(long, value_name = "KEY")]
+	pub add: Vec<String>,
+	/// Public key to stop allowing to post
+	#[arg(long, value_name = "KEY")]
+	pub remove: Vec<String>,
+	/// Public key to make a moderator
+	#[arg(long, value_name = "KEY")]
+	pub add_moderator: Vec<String>,
+	/// Public key to stop being a moderator
+	#[arg(long, value_name = "KEY")]
+	pub remove_moderator: Vec<String>,
+}
 
-	-i  --interactive     run in interactive mode
-	-m  --machine-output  print in a way more sutible for parsing
-	-c  --create          create a new repo (no link required as argument)
-	-v  --version         output version information and exit
-	-h  --help            display this help and exit
+#[derive(Subcommand)]
+pub enum HeaderCommand {
+	/// Print the header of a thread and who signed it
+	Show(LinkArgs),
+	/// Sign a new revision of the header, as the owner
+	Edit(EditHeaderArgs),
+}
 
-To create a new repo run:
-	{program_name} -c
-To edit an existing repo use:
-	{program_name} -i <IPNS link>
+#[derive(Args)]
+pub struct EditHeaderArgs {
+	pub link: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
+	#[arg(long)]
+	pub name: Option<String>,
+	#[arg(long, value_name = "NUMBER")]
+	pub thread_number: Option<u32>,
+	#[arg(long, value_name = "TAG")]
+	pub add_tag: Vec<String>,
+	#[arg(long, value_name = "TAG")]
+	pub remove_tag: Vec<String>,
+}
 
-Send all issues to <https://github.com/Voklen/Crypto-Forum/issues> or <Alex.Gorichev@protonmail.com>
-"
-	);
-	std::process::exit(0)
+#[derive(Subcommand)]
+pub enum ForumCommand {
+	/// Create a new forum with no threads, owned by the account
+	Create(CreateForumArgs),
+	/// Add a thread to a forum, as the owner
+	Add(AddThreadArgs),
+	/// Print a forum's manifest and who signed it
+	Show(ForumLinkArgs),
+	/// Sign a new version of the forum's manifest, as the owner
+	Edit(EditForumArgs),
+	/// List the threads in a forum by number, name and tags
+	List(ForumLinkArgs),
+	/// Print the thread with the number in a forum
+	Open(OpenThreadArgs),
+}
+
+#[derive(Args)]
+pub struct ForumLinkArgs {
+	pub link: String,
+	#[command(flatten)]
+	pub owner: OwnerArgs,
+}
+
+#[derive(Args)]
+pub struct OwnerArgs {
+	/// Public key the forum's manifest must be signed by, otherwise whoever it lists as the owner
+	/// is trusted
+	#[arg(long, value_name = "KEY")]
+	pub owner: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CreateForumArgs {
+	#[arg(long)]
+	pub name: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
+}
+
+#[derive(Args)]
+pub struct AddThreadArgs {
+	pub forum: String,
+	/// IPNS link to the thread
+	pub thread: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
+}
+
+#[derive(Args)]
+pub struct EditForumArgs {
+	pub link: String,
+	#[command(flatten)]
+	pub signer: SignerArgs,
+	#[arg(long)]
+	pub name: Option<String>,
+	/// IPNS link to a thread to stop listing
+	#[arg(long, value_name = "LINK")]
+	pub remove_thread: Vec<String>,
+	/// Where a user's profile is published
+	#[arg(long, value_name = "KEY=LINK", value_parser = parse_profile)]
+	pub add_user: Vec<(String, String)>,
+	/// Public key of a user to stop listing
+	#[arg(long, value_name = "KEY")]
+	pub remove_user: Vec<String>,
+	/// Public key to make a moderator
+	#[arg(long, value_name = "KEY")]
+	pub add_moderator: Vec<String>,
+	/// Public key to stop being a moderator
+	#[arg(long, value_name = "KEY")]
+	pub remove_moderator: Vec<String>,
+	/// IPNS link to a contributor's copy of the manifest
+	#[arg(long, value_name = "LINK")]
+	pub add_mirror: Vec<String>,
+	#[arg(long, value_name = "LINK")]
+	pub remove_mirror: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct OpenThreadArgs {
+	pub forum: String,
+	pub thread_number: u32,
+	#[command(flatten)]
+	pub owner: OwnerArgs,
+	#[command(flatten)]
+	pub display: DisplayArgs,
+}
+
+pub fn get_args() -> Cli {
+	Cli::parse()
+}
+
+pub fn get_ipfs_config(store_args: &StoreArgs) -> IpfsConfig {
+	IpfsConfig {
+		api_url: store_args.api.clone(),
+		timeout: timeout_from_secs(store_args.timeout),
+		retries: store_args.retries,
+		credentials: store_args.auth.clone(),
+		..IpfsConfig::default()
+	}
+}
+
+/// A timeout of 0 means wait forever
+fn timeout_from_secs(secs: u64) -> Option<Duration> {
+	match secs {
+		0 => None,
+		secs => Some(Duration::from_secs(secs)),
+	}
+}
+
+/// Public keys are base64 without padding so they never contain `=`
+fn parse_profile(profile: &str) -> Result<(String, String), String> {
+	match profile.split_once('=') {
+		Some((key, link)) if !key.is_empty() && !link.is_empty() => {
+			Ok((key.to_string(), link.to_string()))
+		}
+		_ => Err("expected KEY=LINK".to_string()),
+	}
+}
+
+fn parse_auth(auth: &str) -> Result<(String, String), String> {
+	match auth.split_once(':') {
+		Some((username, password)) => Ok((username.to_string(), password.to_string())),
+		None => Err("expected USER:PASS".to_string()),
+	}
 }
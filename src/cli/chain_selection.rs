@@ -0,0 +1,52 @@
+use crate::input::*;
+use crypto_forum::{chains::*, custom_types::Message};
+
+/// Work out which message the user is replying to, asking them if the thread has split
+pub fn select_last_hash(messages: &[Message]) -> [u8; 64] {
+	let graph = MessageGraph::new(messages);
+	let chains = graph.get_chains();
+	let heads: Vec<&Message> = chains
+		.iter()
+		.filter_map(|chain| chain.last().copied())
+		.collect();
+	match heads.len() {
+		0 => return ROOT_HASH,
+		1 => return heads[0].get_hash(),
+		_ => {}
+	}
+
+	print_forks(&graph.get_forks());
+	eprintln!(
+		"There are {} chains of validly signed messages:",
+		chains.len()
+	);
+	for (number, chain) in chains.iter().enumerate() {
+		print_chain_head(number + 1, chain);
+	}
+	let selection = ask_for_index("Which chain would you like to reply to?", heads.len());
+	heads[selection].get_hash()
+}
+
+fn print_forks(forks: &[Fork]) {
+	for fork in forks {
+		let replying_to = match fork.parent {
+			ROOT_HASH => "the start of the thread".to_string(),
+			_ => format!("message with hash {}", fork.replies[0].prev_hash_string()),
+		};
+		eprintln!(
+			"!!!FORK: {} messages are replying to {replying_to}!!!",
+			fork.replies.len()
+		);
+	}
+}
+
+fn print_chain_head(number: usize, chain: &[&Message]) {
+	let head = chain
+		.last()
+		.expect("Chains always have at least one message");
+	let author = head
+		.get_username()
+		.unwrap_or_else(|| head.public_key_string());
+	eprintln!("{number}: {} messages, last by {author}:", chain.len());
+	eprintln!("{}", head.body);
+}
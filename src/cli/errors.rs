@@ -1,22 +1,122 @@
+use crypto_forum::custom_types::Error;
+use std::{fmt, process::ExitCode};
+
+/// Make a `CliError` of the given kind with a formatted message
+#[macro_export]
+macro_rules! error{
+    ($kind:ident, $($message:tt)*) => {{
+		$crate::errors::CliError::new($crate::errors::ErrorKind::$kind, format!($($message)*))
+    }}
+}
+
+/// Return early with a `CliError` of the given kind with a formatted message
 #[macro_export]
 macro_rules! throw{
-    ($($message:tt)*) => {{
-		use	crate::errors::throw_error_fuction;
-        let res = format!($($message)*);
-        throw_error_fuction(res)
+    ($kind:ident, $($message:tt)*) => {{
+		return Err($crate::error!($kind, $($message)*))
     }}
 }
 
-pub fn throw_error_fuction(error_message: String) -> ! {
-	#[cfg(not(debug_assertions))]
-	exit_production(error_message);
-	#[cfg(debug_assertions)]
-	panic!("{error_message}");
+/// What went wrong, each has its own exit code so scripts can tell them apart
+///
+/// | Code | Kind |
+/// |------|------|
+/// | 1 | Verification, a thread failed `verify` |
+/// | 2 | UserInput, a bad argument, link, account name or password |
+/// | 3 | Network, IPFS couldn't be reached or timed out |
+/// | 4 | Parse, a thread or account file couldn't be read |
+/// | 5 | Crypto, encrypting, decrypting or deriving a key failed |
+/// | 6 | Io, a local file couldn't be read or written |
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+	Verification,
+	UserInput,
+	Network,
+	Parse,
+	Crypto,
+	Io,
+}
+
+impl ErrorKind {
+	pub fn exit_code(self) -> u8 {
+		match self {
+			Self::Verification => 1,
+			// The same as clap uses for bad arguments
+			Self::UserInput => 2,
+			Self::Network => 3,
+			Self::Parse => 4,
+			Self::Crypto => 5,
+			Self::Io => 6,
+		}
+	}
+
+	/// Sort a library error by what most likely caused it
+	pub fn of(error: &Error) -> Self {
+		match error.root() {
+			Error::IPFS(_) | Error::Timeout(_) => Self::Network,
+			Error::TomlSerialization(_)
+			| Error::TomlDeserialization(_)
+			| Error::MsgpackSerialization(_)
+			| Error::MsgpackDeserialization(_)
+			| Error::FromUtf8(_)
+			| Error::InvalidFileData(_) => Self::Parse,
+			Error::Encryption(_) | Error::KeyDerivation(_) | Error::SignatureError(_) => {
+				Self::Crypto
+			}
+			Error::WrongPassword
+			| Error::NotFound(_)
+			| Error::AlreadyExists(_)
+			| Error::InvalidConfig(_)
+			| Error::Unauthorized(_) => Self::UserInput,
+			Error::StdIo(_) => Self::Io,
+			Error::Context { .. } => unreachable!("The root error never has context"),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct CliError {
+	pub kind: ErrorKind,
+	pub message: String,
+}
+
+impl CliError {
+	pub fn new(kind: ErrorKind, message: String) -> Self {
+		Self { kind, message }
+	}
+
+	/// Print the error and get the code to exit with
+	pub fn report(&self) -> ExitCode {
+		let program_name = env!("CARGO_PKG_NAME");
+		eprintln!("{program_name}: {self}");
+		ExitCode::from(self.kind.exit_code())
+	}
+}
+
+impl fmt::Display for CliError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.message)
+	}
+}
+
+impl From<Error> for CliError {
+	fn from(error: Error) -> Self {
+		Self::new(ErrorKind::of(&error), full_message(&error))
+	}
 }
 
-#[allow(dead_code)]
-fn exit_production(error_message: String) -> ! {
-	let program_name = env!("CARGO_PKG_NAME");
-	eprintln!("{program_name}: {error_message}");
-	std::process::exit(1);
+/// The error's message followed by the message of each error that caused it
+///
+/// Stops after the first error from outside the library, as those often include their own causes
+pub fn full_message(error: &Error) -> String {
+	let mut message = error.to_string();
+	let mut source = std::error::Error::source(error);
+	while let Some(cause) = source {
+		message += &format!(": {cause}");
+		source = match cause.downcast_ref::<Error>() {
+			Some(error) => std::error::Error::source(error),
+			None => None,
+		};
+	}
+	message
 }
@@ -0,0 +1,20 @@
+use crate::{arguments::ExportArgs, error, errors::CliError};
+use crypto_forum::{blocking, store::ForumStore};
+use std::io::Write;
+
+/// Print the thread file as it's stored, so it can be backed up or inspected
+///
+/// Binary files are written out as they are, so they should be exported with --output. Messages
+/// stored as their own objects are put in the file, otherwise it would only have their links.
+pub fn export(store: &impl ForumStore, export_args: &ExportArgs) -> Result<(), CliError> {
+	let link = &export_args.link;
+	let file_contents = blocking::read_standalone_file(store, link)?;
+	match &export_args.output {
+		Some(path) => std::fs::write(path, file_contents)
+			.map_err(|e| error!(Io, "Error writing to {path}: {e}"))?,
+		None => std::io::stdout()
+			.write_all(&file_contents)
+			.map_err(|e| error!(Io, "Error writing to stdout: {e}"))?,
+	}
+	Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::{
+	account_manager,
+	arguments::{CreateForumArgs, EditForumArgs, ForumLinkArgs, OpenThreadArgs, OwnerArgs},
+	errors::{full_message, CliError},
+	members_cli, throw,
+};
+use crypto_forum::{
+	blocking,
+	custom_types::{ForumManifest, ProfileLink},
+	forum,
+	store::ForumStore,
+};
+
+pub fn create_forum(
+	store: &impl ForumStore,
+	create_args: &CreateForumArgs,
+	accounts_dir: &str,
+) -> Result<(), CliError> {
+	let keypair = account_manager::open_signing_account(&create_args.signer, accounts_dir)?;
+	let manifest = ForumManifest::new(create_args.name.clone());
+	let forum_link = blocking::new_forum(store, &manifest, &keypair)?;
+	println!("Forum made at link: {forum_link}");
+	Ok(())
+}
+
+pub fn show_forum(store: &impl ForumStore, link_args: &ForumLinkArgs) -> Result<(), CliError> {
+	// Reading fails if the manifest isn't signed by its owner
+	let manifest = get_manifest(store, &link_args.link, &link_args.owner)?;
+	println!("Name: {}", manifest.name);
+	println!("Owner: {}", manifest.owner);
+	println!("Threads: {}", manifest.threads.len());
+	// Who can moderate a thread is decided by its member lists, these aren't enforced
+	println!("Moderators (informational):");
+	for moderator in &manifest.moderators {
+		println!("{moderator}");
+	}
+	println!("Users:");
+	for user in &manifest.users {
+		println!("{} {}", user.public_key, user.link);
+	}
+	println!("Mirrors: {}", manifest.mirrors.join(", "));
+	Ok(())
+}
+
+/// Sign a new version of the manifest with the changes made to the current one
+pub fn edit_forum(
+	store: &impl ForumStore,
+	edit_args: &EditForumArgs,
+	accounts_dir: &str,
+) -> Result<(), CliError> {
+	let link = &edit_args.link;
+	let added_users = edit_args.add_user.iter().map(|(key, _)| key);
+	for key in added_users
+		.chain(&edit_args.remove_user)
+		.chain(&edit_args.add_moderator)
+		.chain(&edit_args.remove_moderator)
+	{
+		members_cli::check_public_key(key)?;
+	}
+
+	let mut manifest = blocking::get_manifest(store, link, None)?;
+	if let Some(name) = &edit_args.name {
+		manifest.name = name.clone();
+	}
+	manifest
+		.threads
+		.retain(|thread| !edit_args.remove_thread.contains(thread));
+	// Adding a user that is already listed replaces the link to their profile
+	manifest.users.retain(|user| {
+		let replaced = edit_args
+			.add_user
+			.iter()
+			.any(|(key, _)| key == &user.public_key);
+		!replaced && !edit_args.remove_user.contains(&user.public_key)
+	});
+	for (public_key, link) in &edit_args.add_user {
+		manifest.users.push(ProfileLink {
+			public_key: public_key.clone(),
+			link: link.clone(),
+		});
+	}
+	members_cli::apply_changes(
+		&mut manifest.moderators,
+		&edit_args.add_moderator,
+		&edit_args.remove_moderator,
+	);
+	members_cli::apply_changes(
+		&mut manifest.mirrors,
+		&edit_args.add_mirror,
+		&edit_args.remove_mirror,
+	);
+
+	let keypair = account_manager::open_signing_account(&edit_args.signer, accounts_dir)?;
+	manifest.sign(link, &keypair);
+	blocking::write_manifest(store, link, &manifest)?;
+	println!("Updated the forum at {link}");
+	Ok(())
+}
+
+pub fn list_threads(store: &impl ForumStore, link_args: &ForumLinkArgs) -> Result<(), CliError> {
+	let link = &link_args.link;
+	let threads = list_forum_threads(store, link, &link_args.owner)?;
+	if threads.is_empty() {
+		println!("The forum at {link} has no threads");
+	}
+	for thread in &threads {
+		// Unreadable threads are listed last, so the warnings come after the rest
+		let header = match &thread.header {
+			Ok(header) => header,
+			Err(e) => {
+				eprintln!("Could not read thread {}: {}", thread.link, full_message(e));
+				continue;
+			}
+		};
+		let tags = match header.tags.is_empty() {
+			true => String::new(),
+			false => format!(" [{}]", header.tags.join(", ")),
+		};
+		println!("{}. {}{tags}", header.thread_number, header.name);
+		println!("\t{}", thread.link);
+	}
+	Ok(())
+}
+
+/// Get the link to the thread with the number in the forum
+pub fn find_thread(
+	store: &impl ForumStore,
+	open_args: &OpenThreadArgs,
+) -> Result<String, CliError> {
+	let forum_link = &open_args.forum;
+	let thread_number = open_args.thread_number;
+	let threads = list_forum_threads(store, forum_link, &open_args.owner)?;
+	match forum::find_thread(&threads, thread_number) {
+		Some(thread) => Ok(thread.link.clone()),
+		None => throw!(
+			UserInput,
+			"The forum at {forum_link} has no thread numbered {thread_number}"
+		),
+	}
+}
+
+fn get_manifest(
+	store: &impl ForumStore,
+	link: &str,
+	owner_args: &OwnerArgs,
+) -> Result<ForumManifest, CliError> {
+	let owner = checked_owner(owner_args)?;
+	Ok(blocking::get_manifest(store, link, owner)?)
+}
+
+fn list_forum_threads(
+	store: &impl ForumStore,
+	link: &str,
+	owner_args: &OwnerArgs,
+) -> Result<Vec<forum::ForumThread>, CliError> {
+	let owner = checked_owner(owner_args)?;
+	Ok(blocking::list_threads(store, link, owner)?)
+}
+
+fn checked_owner(owner_args: &OwnerArgs) -> Result<Option<&str>, CliError> {
+	if let Some(owner) = &owner_args.owner {
+		members_cli::check_public_key(owner)?;
+	}
+	Ok(owner_args.owner.as_deref())
+}
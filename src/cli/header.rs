@@ -0,0 +1,54 @@
+use crate::{account_manager, arguments::EditHeaderArgs, errors::CliError};
+use crypto_forum::{
+	blocking,
+	custom_types::{Header, HeaderRevision},
+	store::ForumStore,
+};
+
+pub fn show_header(store: &impl ForumStore, link: &str) -> Result<(), CliError> {
+	let header = blocking::get_header(store, link)?;
+	print_header(&header);
+	// Reading fails if there are revisions but none are signed by the owner
+	match header.revisions.len() {
+		0 => println!("Not signed, anyone republishing the thread could have changed this"),
+		count => println!("Signed by the owner {} ({count} revisions)", header.owner),
+	}
+	Ok(())
+}
+
+pub fn print_header(header: &Header) {
+	println!("Name: {}", header.name);
+	println!("Thread number: {}", header.thread_number);
+	println!("Tags: {}", header.tags.join(", "));
+	println!("Mirrors: {}", header.mirrors.join(", "));
+}
+
+/// Sign a new revision of the header with the changes made to the current one
+pub fn edit_header(
+	store: &impl ForumStore,
+	edit_args: &EditHeaderArgs,
+	accounts_dir: &str,
+) -> Result<(), CliError> {
+	let link = &edit_args.link;
+	let mut header = blocking::get_header(store, link)?;
+	if let Some(name) = &edit_args.name {
+		header.name = name.clone();
+	}
+	if let Some(thread_number) = edit_args.thread_number {
+		header.thread_number = thread_number;
+	}
+	header
+		.tags
+		.retain(|tag| !edit_args.remove_tag.contains(tag));
+	for tag in &edit_args.add_tag {
+		if !header.tags.contains(tag) {
+			header.tags.push(tag.clone());
+		}
+	}
+
+	let keypair = account_manager::open_signing_account(&edit_args.signer, accounts_dir)?;
+	let revision = HeaderRevision::new_signed(&header, link, &keypair);
+	blocking::edit_header(store, link, revision)?;
+	println!("Updated the header of {link}");
+	Ok(())
+}
@@ -1,11 +1,11 @@
 pub fn input(prompt: &str) -> String {
-	println!("{prompt}");
+	eprintln!("{prompt}");
 
 	let mut input_string = String::new();
 	let read_result = std::io::stdin().read_line(&mut input_string);
 
 	if read_result.is_err() {
-		println!("Could not read the input, try again.");
+		eprintln!("Could not read the input, try again.");
 		return input(prompt);
 	};
 	input_string.trim().into()
@@ -24,8 +24,31 @@ pub fn ask_for_bool(prompt: &str) -> bool {
 		"no" => false,
 		"false" => false,
 		_ => {
-			println!("Please type y(es) or n(o)");
+			eprintln!("Please type y(es) or n(o)");
 			ask_for_bool(prompt)
 		}
 	}
 }
+
+/// Ask the user to pick one of `count` options numbered from 1, returns the index of the option
+pub fn ask_for_index(prompt: &str, count: usize) -> usize {
+	let input = input(prompt);
+	match input.parse::<usize>() {
+		Ok(number) if (1..=count).contains(&number) => number - 1,
+		_ => {
+			eprintln!("Please type a number from 1 to {count}");
+			ask_for_index(prompt, count)
+		}
+	}
+}
+
+pub fn ask_for_number(prompt: &str) -> u32 {
+	let input = input(prompt);
+	match input.parse::<u32>() {
+		Ok(number) => number,
+		_ => {
+			eprintln!("Please type a whole number");
+			ask_for_number(prompt)
+		}
+	}
+}
@@ -1,8 +1,20 @@
-use crate::{input::*, throw, write, Message};
-use crypto_forum::custom_types::FullFile;
+use crate::{
+	account_manager,
+	arguments::{CreateArgs, SignerArgs},
+	errors::CliError,
+	header_cli,
+	input::*,
+	Message,
+};
+use crypto_forum::{blocking, custom_types::Header, store::ForumStore};
 use ed25519_dalek::*;
 
-pub fn interactive_write(link: &str, keypair: Keypair, last_hash: [u8; 64]) {
+pub fn interactive_write(
+	store: &impl ForumStore,
+	link: &str,
+	keypair: Keypair,
+	last_hash: [u8; 64],
+) -> Result<(), CliError> {
 	let write_data = Vec::<Message>::new();
 
 	// THIS BREAKS IF THEIR KEY SEED IS ALL 0'S
@@ -14,11 +26,8 @@ pub fn interactive_write(link: &str, keypair: Keypair, last_hash: [u8; 64]) {
 	};
 
 	let messages = get_messages_from_user(&keypair, write_data, last_hash, &bad_keypair);
-	let write_result = write::write_messages(link, messages);
-	if write_result.is_err() {
-		println!("Failed to write to file");
-		interactive_write(link, keypair, last_hash)
-	};
+	blocking::write_messages(store, link, messages)?;
+	Ok(())
 }
 
 fn get_messages_from_user(
@@ -51,16 +60,53 @@ fn get_messages_from_user(
 	get_messages_from_user(keypair, write_data, new_hash, bad_keypair)
 }
 
-/// Creates a new GitArk repo, returns the link to the repo
-/// # Errors
-/// The program will quit with an error message
-pub fn new_repo() -> String {
-	//TODO Ask user for repo metadata
-	match write::new_ipns() {
-		Ok(ipns_link) => {
-			println!("Repo made at link: {ipns_link}");
-			ipns_link
+/// Creates a new GitArk repo owned by the user's account, returns the link to the repo
+pub fn new_repo(
+	store: &impl ForumStore,
+	create_args: &CreateArgs,
+	accounts_dir: &str,
+) -> Result<String, CliError> {
+	let header = get_header(create_args);
+	let keypair = match &create_args.account {
+		Some(account) => {
+			let signer = SignerArgs {
+				account: account.clone(),
+				password_fd: create_args.password_fd,
+			};
+			account_manager::open_signing_account(&signer, accounts_dir)?
 		}
-		Err(err) => throw!("Failed to create repo with error: {err}"),
+		None => account_manager::login(accounts_dir)?,
+	};
+	let ipns_link = blocking::new_owned_ipns(store, header.clone(), &keypair)?;
+	println!("Repo made at link: {ipns_link}");
+	header_cli::print_header(&header);
+	Ok(ipns_link)
+}
+
+/// Use the name, number and tags from the arguments, asking for any that weren't given
+fn get_header(create_args: &CreateArgs) -> Header {
+	let name = match &create_args.name {
+		Some(name) => name.clone(),
+		None => input("Please enter the name of the thread"),
+	};
+	let thread_number = match create_args.thread_number {
+		Some(thread_number) => thread_number,
+		None => ask_for_number("Please enter the thread number"),
+	};
+	let tags = match create_args.tag.as_slice() {
+		[] if create_args.no_tags => Vec::new(),
+		[] => input("Please enter the tags separated by commas (leave empty for none)")
+			.split(',')
+			.map(str::trim)
+			.filter(|tag| !tag.is_empty())
+			.map(str::to_string)
+			.collect(),
+		tags => tags.to_vec(),
+	};
+	Header {
+		name,
+		thread_number,
+		tags,
+		..Header::new()
 	}
 }
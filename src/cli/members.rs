@@ -0,0 +1,91 @@
+use crate::{account_manager, arguments::EditMembersArgs, errors::CliError, throw};
+use base64::{engine::general_purpose, Engine};
+use crypto_forum::{
+	blocking,
+	custom_types::{HeaderRevision, MemberList},
+	members::Membership,
+	store::ForumStore,
+};
+use ed25519_dalek::PublicKey;
+
+pub fn show_members(store: &impl ForumStore, link: &str) -> Result<(), CliError> {
+	let header = blocking::get_header(store, link)?;
+	let Some(membership) = Membership::from_header(&header, link) else {
+		println!("The thread at {link} has no member list, anyone can post");
+		return Ok(());
+	};
+	println!("Owner: {}", membership.owner);
+	println!("Moderators:");
+	for moderator in &membership.moderators {
+		println!("{moderator}");
+	}
+	println!("Members:");
+	for member in &membership.members {
+		println!("{member}");
+	}
+	Ok(())
+}
+
+/// Sign a new version of the member list with the changes made to the current one
+pub fn edit_members(
+	store: &impl ForumStore,
+	edit_args: &EditMembersArgs,
+	accounts_dir: &str,
+) -> Result<(), CliError> {
+	let link = &edit_args.link;
+	for key in [
+		&edit_args.add,
+		&edit_args.remove,
+		&edit_args.add_moderator,
+		&edit_args.remove_moderator,
+	]
+	.into_iter()
+	.flatten()
+	{
+		check_public_key(key)?;
+	}
+
+	let header = blocking::get_header(store, link)?;
+	let current = Membership::from_header(&header, link);
+	let (mut members, mut moderators) = match &current {
+		Some(membership) => (membership.members.clone(), membership.moderators.clone()),
+		None => (Vec::new(), Vec::new()),
+	};
+	apply_changes(&mut members, &edit_args.add, &edit_args.remove);
+	apply_changes(
+		&mut moderators,
+		&edit_args.add_moderator,
+		&edit_args.remove_moderator,
+	);
+
+	let keypair = account_manager::open_signing_account(&edit_args.signer, accounts_dir)?;
+	if header.owner.is_empty() {
+		// The owner is whoever signs the first header revision, so sign one to claim the thread
+		let revision = HeaderRevision::new_signed(&header, link, &keypair);
+		blocking::edit_header(store, link, revision)?;
+		println!("Signed the header of {link}, you are now its owner");
+	}
+	let member_list = MemberList::new_signed(&header, link, members, moderators, &keypair);
+	blocking::add_member_list(store, link, member_list)?;
+	println!("Updated the members of {link}");
+	Ok(())
+}
+
+/// Remove then add the values, without adding any that are already there
+pub fn apply_changes(values: &mut Vec<String>, add: &[String], remove: &[String]) {
+	values.retain(|value| !remove.contains(value));
+	for value in add {
+		if !values.contains(value) {
+			values.push(value.clone());
+		}
+	}
+}
+
+pub fn check_public_key(key: &str) -> Result<(), CliError> {
+	let base64 = general_purpose::STANDARD_NO_PAD;
+	let bytes = base64.decode(key).unwrap_or_default();
+	match PublicKey::from_bytes(&bytes) {
+		Ok(_) => Ok(()),
+		Err(_) => throw!(UserInput, "Invalid public key: {key}"),
+	}
+}
@@ -0,0 +1,26 @@
+use crate::{arguments::MigrateArgs, errors::CliError};
+use crypto_forum::{blocking, migrate::FORMAT_VERSION, store::ForumStore};
+
+/// Republish each thread that isn't in the newest format version
+pub fn migrate(store: &impl ForumStore, migrate_args: &MigrateArgs) -> Result<(), CliError> {
+	for link in &migrate_args.links {
+		match migrate_args.forum {
+			true => {
+				let manifest = blocking::get_manifest(store, link, None)?;
+				for thread in &manifest.threads {
+					migrate_thread(store, thread)?
+				}
+			}
+			false => migrate_thread(store, link)?,
+		}
+	}
+	Ok(())
+}
+
+fn migrate_thread(store: &impl ForumStore, link: &str) -> Result<(), CliError> {
+	match blocking::migrate_repo(store, link)? {
+		FORMAT_VERSION => println!("{link} is already in format version {FORMAT_VERSION}"),
+		version => println!("Migrated {link} from format version {version} to {FORMAT_VERSION}"),
+	}
+	Ok(())
+}
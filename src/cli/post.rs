@@ -0,0 +1,78 @@
+use crate::{account_manager, arguments::PostArgs, error, errors::CliError, throw};
+use base64::{engine::general_purpose, Engine};
+use crypto_forum::{
+	blocking, chains, custom_types::Message, linked::MessageCache, store::ForumStore,
+};
+use std::io::Read;
+
+/// Post a single message without asking the user anything, so it can be used from scripts
+pub fn post(
+	store: &impl ForumStore,
+	post_args: &PostArgs,
+	accounts_dir: &str,
+) -> Result<(), CliError> {
+	let link = &post_args.link;
+
+	let body = get_body(post_args)?;
+	let keypair = account_manager::open_signing_account(&post_args.signer, accounts_dir)?;
+	// Messages fetched to find what to reply to aren't fetched again when writing
+	let mut cache = MessageCache::new();
+	let prev_hash = get_prev_hash(store, link, post_args, &mut cache)?;
+
+	let message = Message::new_signed(body, prev_hash, &keypair);
+	let hash = message.hash_string();
+	blocking::write_messages_cached(store, link, vec![message], &mut cache)?;
+	println!("Posted message with hash: {hash}");
+	Ok(())
+}
+
+/// The body is taken from --message, then --file, then stdin
+fn get_body(post_args: &PostArgs) -> Result<String, CliError> {
+	if let Some(message) = &post_args.message {
+		return Ok(message.clone());
+	}
+	let body = match &post_args.file {
+		Some(file) => std::fs::read_to_string(file)
+			.map_err(|e| error!(Io, "Error reading message from {file}: {e}"))?,
+		None => {
+			let mut body = String::new();
+			std::io::stdin()
+				.read_to_string(&mut body)
+				.map_err(|e| error!(Io, "Error reading message from stdin: {e}"))?;
+			body
+		}
+	};
+	// Files and stdin nearly always end with a newline that isn't meant to be part of the message
+	let body = body.strip_suffix('\n').unwrap_or(&body);
+	if body.is_empty() {
+		throw!(UserInput, "Cannot post an empty message")
+	}
+	Ok(body.to_string())
+}
+
+/// Reply to --reply-to if given, otherwise to the end of the only chain in the thread
+fn get_prev_hash(
+	store: &impl ForumStore,
+	link: &str,
+	post_args: &PostArgs,
+	cache: &mut MessageCache,
+) -> Result<[u8; 64], CliError> {
+	if let Some(hash) = &post_args.reply_to {
+		let base64 = general_purpose::STANDARD_NO_PAD;
+		let bytes = base64.decode(hash).unwrap_or_default();
+		return bytes
+			.try_into()
+			.map_err(|_| error!(UserInput, "Invalid hash to reply to: {hash}"));
+	}
+
+	let chain_ends = blocking::get_chain_ends(store, link, cache)?;
+	match chain_ends.as_slice() {
+		[] => Ok(chains::ROOT_HASH),
+		[end] => Ok(*end),
+		_ => throw!(
+			UserInput,
+			"The thread at {link} has split into {} chains, use --reply-to=<HASH> to pick one",
+			chain_ends.len()
+		),
+	}
+}
@@ -1,28 +1,141 @@
-use crypto_forum::custom_types::*;
+use crypto_forum::{chains::*, custom_types::*, members::*, mirrors::MirroredMessage};
 
-pub fn output_for_human(messages: &Vec<Message>) {
-	for message in messages {
-		let username = message.get_username();
-		let prev_hash = message.prev_hash_string();
-		let hash = message.hash_string();
-		let body = &message.body;
-		println!("--------");
-		if !message.is_signed() {
-			println!("!!!WARNING: INVALID SIGNATURE!!!");
-			println!("!!!WE HAVE NO PROOF THIS PUBLIC KEY EVER POSTED THIS!!!");
+/// How many messages of a reply subthread are shown before the rest are collapsed
+const COLLAPSE_AFTER: usize = 10;
+
+/// What to show alongside the messages in the tree
+pub struct HumanOptions<'a> {
+	/// Show every reply instead of collapsing long subthreads
+	pub expand: bool,
+	/// Warn about messages from keys that aren't members, if the thread has a member list
+	pub flag_members: Option<&'a Membership>,
+}
+
+pub fn output_for_human(messages: &[Message], options: &HumanOptions) {
+	let graph = MessageGraph::new(messages);
+	for root in graph.get_roots() {
+		if root.prev_hash != ROOT_HASH {
+			println!("!!!ORPHANED: THE MESSAGE THIS REPLIES TO IS NOT IN THE THREAD!!!");
+			println!(
+				"Replying to missing message with hash: {}",
+				root.prev_hash_string()
+			);
 		}
-		match username {
-			Some(name) => println!("Public key: {name}"),
-			None => println!("Public key: {}", message.public_key_string()),
+		// Only the subthreads are collapsed, the main thread is always shown in full
+		print_thread(&graph, root, "", None, options);
+	}
+}
+
+/// Print the message and its replies, stopping once `limit` messages have been printed
+fn print_thread(
+	graph: &MessageGraph,
+	first: &Message,
+	prefix: &str,
+	mut limit: Option<usize>,
+	options: &HumanOptions,
+) {
+	let mut message = first;
+	loop {
+		if limit == Some(0) {
+			let hidden = graph.count_subthread(message);
+			println!("{prefix}[{hidden} more messages hidden, use --expand to show them]");
+			return;
 		}
-		println!("Replying to message with hash: {prev_hash}");
-		println!("Message: \n{body}");
-		println!("Hash: {hash}");
+		print_message(message, prefix, options.flag_members);
+		limit = limit.map(|limit| limit - 1);
+
+		// A single reply carries on the conversation so it stays at the same level
+		let replies = graph.get_replies(&message.get_hash());
+		match replies.as_slice() {
+			[] => return,
+			[reply] => message = reply,
+			_ => return print_branches(graph, &replies, prefix, options),
+		}
+	}
+}
+
+fn print_branches(
+	graph: &MessageGraph,
+	replies: &[&Message],
+	prefix: &str,
+	options: &HumanOptions,
+) {
+	let limit = if options.expand {
+		None
+	} else {
+		Some(COLLAPSE_AFTER)
+	};
+	for (index, reply) in replies.iter().enumerate() {
+		let is_last = index == replies.len() - 1;
+		let (branch, continuation) = if is_last {
+			("└── ", "    ")
+		} else {
+			("├── ", "│   ")
+		};
+		println!("{prefix}{branch}Reply {} of {}", index + 1, replies.len());
+		let branch_prefix = format!("{prefix}{continuation}");
+		print_thread(graph, reply, &branch_prefix, limit, options);
+	}
+}
+
+fn print_message(message: &Message, prefix: &str, flag_members: Option<&Membership>) {
+	let username = message.get_username();
+	let hash = message.hash_string();
+	println!("{prefix}--------");
+	if !message.is_signed() {
+		println!("{prefix}!!!WARNING: INVALID SIGNATURE!!!");
+		println!("{prefix}!!!WE HAVE NO PROOF THIS PUBLIC KEY EVER POSTED THIS!!!");
+	}
+	let is_member = flag_members.map(|membership| membership.status_of(message).is_member());
+	if is_member == Some(false) {
+		println!("{prefix}!!!NOT A MEMBER: THIS KEY ISN'T IN THE THREAD'S MEMBER LIST!!!");
+	}
+	match username {
+		Some(name) => println!("{prefix}Public key: {name}"),
+		None => println!("{prefix}Public key: {}", message.public_key_string()),
+	}
+	println!("{prefix}Message: ");
+	for line in message.body.lines() {
+		println!("{prefix}{line}");
+	}
+	println!("{prefix}Hash: {hash}");
+	println!("{prefix}--------")
+}
+
+/// Print the messages that couldn't be decoded so tampering doesn't go unnoticed
+pub fn output_rejected_for_human(verified_messages: &[VerifiedMessage]) {
+	for verified in verified_messages {
+		if verified.message.is_some() {
+			continue;
+		}
+		let file_message = &verified.file_message;
+		println!("--------");
+		println!("!!!REJECTED MESSAGE: {}!!!", verified.status);
+		println!("Public key: {}", file_message.public_key);
+		println!("Replying to message with hash: {}", file_message.prev_hash);
+		println!("Signature: {}", file_message.signature);
+		println!("Message: \n{}", file_message.body);
 		println!("--------")
 	}
 }
 
-pub fn output_for_machine(messages: &Vec<Message>) {
+/// Print where each message was found when reading mirrors
+pub fn output_sources_for_human(mirrored_messages: &[MirroredMessage]) {
+	println!("Sources:");
+	for mirrored in mirrored_messages {
+		let verified = &mirrored.verified;
+		let hash = match &verified.message {
+			Some(message) => message.hash_string(),
+			None => format!(
+				"rejected message with signature {}",
+				verified.file_message.signature
+			),
+		};
+		println!("{hash}: {}", mirrored.sources.join(", "));
+	}
+}
+
+pub fn output_for_machine(messages: &[Message]) {
 	for message in messages {
 		let public_key = message.public_key_string();
 		let username_result = message.get_username();
@@ -42,3 +155,124 @@ pub fn output_for_machine(messages: &Vec<Message>) {
 		}
 	}
 }
+
+/// Changed whenever a field is removed or changes meaning, adding a field doesn't change it
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+#[derive(serde::Serialize)]
+pub struct JsonThread {
+	schema_version: u32,
+	link: String,
+	messages: Vec<JsonMessage>,
+}
+
+/// A line of NDJSON, which carries the version and link itself as there's no enclosing document
+#[derive(serde::Serialize)]
+struct JsonLine<'a> {
+	schema_version: u32,
+	link: &'a str,
+	#[serde(flatten)]
+	message: JsonMessage,
+}
+
+/// Every message in the file, including rejected ones, with the fields as they are in the file
+#[derive(serde::Serialize)]
+struct JsonMessage {
+	public_key: String,
+	username: Option<String>,
+	prev_hash: String,
+	/// `None` if the message couldn't be decoded
+	hash: Option<String>,
+	signed: bool,
+	body: String,
+	status: &'static str,
+	/// The field that couldn't be decoded, if that's why it was rejected
+	field: Option<String>,
+	/// The links the message was found at, more than one if reading mirrors
+	sources: Vec<String>,
+	/// `None` if the thread has no member list or the message couldn't be decoded
+	member: Option<&'static str>,
+}
+
+impl JsonMessage {
+	fn new(mirrored: &MirroredMessage, membership: Option<&Membership>) -> Self {
+		let verified = &mirrored.verified;
+		let file_message = &verified.file_message;
+		let message = verified.message.as_ref();
+		let (status, field) = status_to_json(verified.status);
+		Self {
+			public_key: file_message.public_key.clone(),
+			username: message.and_then(Message::get_username),
+			prev_hash: file_message.prev_hash.clone(),
+			hash: message.map(Message::hash_string),
+			signed: verified.status == MessageStatus::Valid,
+			body: file_message.body.clone(),
+			status,
+			field: field.map(|field| field.to_string()),
+			sources: mirrored.sources.clone(),
+			member: message
+				.zip(membership)
+				.map(|(message, membership)| member_to_json(membership.status_of(message))),
+		}
+	}
+}
+
+/// Names that won't change if the wording for humans does
+fn status_to_json(status: MessageStatus) -> (&'static str, Option<MessageField>) {
+	match status {
+		MessageStatus::Valid => ("valid", None),
+		MessageStatus::BadBase64(field) => ("bad_base64", Some(field)),
+		MessageStatus::WrongLength(field) => ("wrong_length", Some(field)),
+		MessageStatus::InvalidPublicKey => ("invalid_public_key", None),
+		MessageStatus::InvalidSignature => ("invalid_signature", None),
+		MessageStatus::BadSignature => ("bad_signature", None),
+	}
+}
+
+fn member_to_json(status: MemberStatus) -> &'static str {
+	match status {
+		MemberStatus::Owner => "owner",
+		MemberStatus::Moderator => "moderator",
+		MemberStatus::Member => "member",
+		MemberStatus::Unknown => "unknown",
+	}
+}
+
+pub fn json_thread(
+	link: &str,
+	mirrored_messages: &[MirroredMessage],
+	membership: Option<&Membership>,
+) -> JsonThread {
+	JsonThread {
+		schema_version: JSON_SCHEMA_VERSION,
+		link: link.to_string(),
+		messages: mirrored_messages
+			.iter()
+			.map(|mirrored| JsonMessage::new(mirrored, membership))
+			.collect(),
+	}
+}
+
+/// Print every thread as one array, so reading several links is still a single JSON document
+pub fn output_json(threads: &[JsonThread]) {
+	println!("{}", to_json(&threads));
+}
+
+pub fn output_ndjson(
+	link: &str,
+	mirrored_messages: &[MirroredMessage],
+	membership: Option<&Membership>,
+) {
+	for mirrored in mirrored_messages {
+		let line = JsonLine {
+			schema_version: JSON_SCHEMA_VERSION,
+			link,
+			message: JsonMessage::new(mirrored, membership),
+		};
+		println!("{}", to_json(&line));
+	}
+}
+
+fn to_json(value: &impl serde::Serialize) -> String {
+	serde_json::to_string(value).expect("Only has string keys so it can always be serialized")
+}
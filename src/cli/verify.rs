@@ -0,0 +1,39 @@
+use crate::errors::CliError;
+use crypto_forum::{audit::*, blocking, custom_types::MessageStatus, store::ForumStore};
+
+/// Check every file, printing a summary of each, returns whether they were all intact
+pub fn verify_files(store: &impl ForumStore, links: &[String]) -> Result<bool, CliError> {
+	let mut all_intact = true;
+	for link in links {
+		all_intact &= verify_file(store, link)?;
+	}
+	Ok(all_intact)
+}
+
+fn verify_file(store: &impl ForumStore, link: &str) -> Result<bool, CliError> {
+	println!("File: {link}");
+	let verified_messages = blocking::get_verified_messages(store, link)?;
+	let report = audit_messages(&verified_messages);
+
+	for failure in report.failures() {
+		let number = failure.index + 1;
+		if failure.status != MessageStatus::Valid {
+			println!("Message {number}: {}", failure.status);
+		}
+		if let Some(problem) = failure.link_problem {
+			println!("Message {number}: {problem}");
+		}
+	}
+	let failures = report.failures().count();
+	println!("Messages: {}", report.entries.len());
+	println!("Failed: {failures}");
+	println!("Forks: {}", report.forks);
+
+	let intact = report.is_intact();
+	if intact {
+		println!("Result: OK");
+	} else {
+		println!("Result: FAILED");
+	}
+	Ok(intact)
+}
@@ -1,9 +1,37 @@
+#[path = "backend/account_file.rs"]
+pub mod account_file;
+#[path = "backend/audit.rs"]
+pub mod audit;
+#[path = "backend/blocking.rs"]
+pub mod blocking;
+#[path = "backend/chains.rs"]
+pub mod chains;
 #[path = "backend/custom_types.rs"]
 pub mod custom_types;
 
+#[path = "backend/encoding.rs"]
+pub mod encoding;
 #[path = "backend/encrypt_decrypt.rs"]
 pub mod encrypt_decrypt;
+#[path = "backend/forum.rs"]
+pub mod forum;
+#[path = "backend/header.rs"]
+pub mod header;
+#[path = "backend/ipfs_store.rs"]
+pub mod ipfs_store;
+#[path = "backend/linked.rs"]
+pub mod linked;
+#[path = "backend/local_store.rs"]
+pub mod local_store;
+#[path = "backend/members.rs"]
+pub mod members;
+#[path = "backend/migrate.rs"]
+pub mod migrate;
+#[path = "backend/mirrors.rs"]
+pub mod mirrors;
 #[path = "backend/read.rs"]
 pub mod read;
+#[path = "backend/store.rs"]
+pub mod store;
 #[path = "backend/write.rs"]
 pub mod write;
@@ -1,68 +1,287 @@
-use arguments::Argument;
-use crypto_forum::{custom_types::*, *};
+use arguments::*;
+use crypto_forum::{
+	custom_types::*,
+	ipfs_store::IpfsStore,
+	local_store::LocalStore,
+	members::Membership,
+	mirrors::{MirroredMessage, MirroredThread},
+	store::ForumStore,
+	*,
+};
 
-use crate::print_messages::*;
+use crate::{errors::*, print_messages::*};
+use base64::{engine::general_purpose, Engine};
+use std::process::ExitCode;
 
 #[path = "cli/account_manager.rs"]
 mod account_manager;
 #[path = "cli/arguments.rs"]
 mod arguments;
+#[path = "cli/chain_selection.rs"]
+mod chain_selection;
 #[path = "cli/errors.rs"]
 mod errors;
+#[path = "cli/export.rs"]
+mod export;
+#[path = "cli/forum.rs"]
+mod forum_cli;
+#[path = "cli/header.rs"]
+mod header_cli;
 #[path = "cli/input.rs"]
 mod input;
+#[path = "cli/members.rs"]
+mod members_cli;
+#[path = "cli/migrate.rs"]
+mod migrate_cli;
+#[path = "cli/post.rs"]
+mod post;
 #[path = "cli/print_messages.rs"]
 mod print_messages;
+#[path = "cli/verify.rs"]
+mod verify_cli;
 #[path = "cli/interactive_write.rs"]
 mod write_cli;
 
-fn main() {
-	let (links, arguments) = arguments::get_args();
-	if arguments.contains(&Argument::Create) {
-		let created_link = write_cli::new_repo();
-		process_file(&created_link, &arguments);
-		return;
+const ACCOUNTS_DIR: &str = "reference/accounts/";
+
+fn main() -> ExitCode {
+	let cli = arguments::get_args();
+	let result = match &cli.store.local {
+		Some(dir) => LocalStore::new(dir)
+			.map_err(|e| {
+				error!(
+					UserInput,
+					"Error opening local store at {dir}: {}",
+					full_message(&e)
+				)
+			})
+			.and_then(|store| run(&store, cli.command)),
+		None => {
+			let config = arguments::get_ipfs_config(&cli.store);
+			IpfsStore::with_config(config)
+				.map_err(|e| {
+					error!(
+						UserInput,
+						"Error starting IPFS client: {}",
+						full_message(&e)
+					)
+				})
+				.and_then(|store| run(&store, cli.command))
+		}
+	};
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(error) => error.report(),
 	}
-	for messages_file in &links {
-		process_file(messages_file, &arguments)
+}
+
+fn run(store: &impl ForumStore, command: Command) -> Result<(), CliError> {
+	match command {
+		Command::Read(read_args) => process_files(store, &read_args.links, &read_args.display)?,
+		Command::Post(post_args) => post::post(store, &post_args, ACCOUNTS_DIR)?,
+		Command::Create(create_args) => {
+			let created_link = write_cli::new_repo(store, &create_args, ACCOUNTS_DIR)?;
+			process_files(store, &[created_link], &create_args.display)?;
+		}
+		Command::Account(AccountCommand::List) => account_manager::list_accounts(ACCOUNTS_DIR)?,
+		Command::Account(AccountCommand::New) => {
+			let keypair = account_manager::new_account(ACCOUNTS_DIR)?;
+			let public_key = general_purpose::STANDARD_NO_PAD.encode(keypair.public.as_bytes());
+			println!("Created account with public key: {public_key}");
+		}
+		Command::Verify(links_args) => {
+			let all_intact = verify_cli::verify_files(store, &links_args.links)?;
+			if !all_intact {
+				throw!(Verification, "Not every thread is intact")
+			}
+		}
+		Command::Export(export_args) => export::export(store, &export_args)?,
+		Command::Convert(convert_args) => {
+			let link = &convert_args.link;
+			if let Some(to) = convert_args.to {
+				blocking::convert_repo(store, link, to.file_format())?;
+			}
+			if convert_args.linked {
+				blocking::link_messages(store, link)?;
+			}
+			println!("Republished {link}");
+		}
+		Command::Migrate(migrate_args) => migrate_cli::migrate(store, &migrate_args)?,
+		Command::Members(MembersCommand::Show(link_args)) => {
+			members_cli::show_members(store, &link_args.link)?
+		}
+		Command::Members(MembersCommand::Edit(edit_args)) => {
+			members_cli::edit_members(store, &edit_args, ACCOUNTS_DIR)?
+		}
+		Command::Header(HeaderCommand::Show(link_args)) => {
+			header_cli::show_header(store, &link_args.link)?
+		}
+		Command::Header(HeaderCommand::Edit(edit_args)) => {
+			header_cli::edit_header(store, &edit_args, ACCOUNTS_DIR)?
+		}
+		Command::Forum(ForumCommand::Create(create_args)) => {
+			forum_cli::create_forum(store, &create_args, ACCOUNTS_DIR)?
+		}
+		Command::Forum(ForumCommand::Add(add_args)) => {
+			let keypair = account_manager::open_signing_account(&add_args.signer, ACCOUNTS_DIR)?;
+			blocking::add_thread(store, &add_args.forum, &add_args.thread, &keypair)?;
+			println!("Added {} to {}", add_args.thread, add_args.forum);
+		}
+		Command::Forum(ForumCommand::Show(link_args)) => forum_cli::show_forum(store, &link_args)?,
+		Command::Forum(ForumCommand::Edit(edit_args)) => {
+			forum_cli::edit_forum(store, &edit_args, ACCOUNTS_DIR)?
+		}
+		Command::Forum(ForumCommand::List(link_args)) => {
+			forum_cli::list_threads(store, &link_args)?
+		}
+		Command::Forum(ForumCommand::Open(open_args)) => {
+			let link = forum_cli::find_thread(store, &open_args)?;
+			process_files(store, &[link], &open_args.display)?;
+		}
+		Command::Mirror(mirror_args) => {
+			let keypair = account_manager::open_signing_account(&mirror_args.signer, ACCOUNTS_DIR)?;
+			blocking::add_mirror(store, &mirror_args.link, &mirror_args.mirror, &keypair)?;
+			println!("Added {} as a mirror", mirror_args.mirror);
+		}
 	}
+	Ok(())
 }
 
-pub fn process_file(link: &str, arguments: &[Argument]) {
-	println!("File: {link}");
-	let messages = get_messages(link);
+/// Print the threads, JSON is printed once they've all been read so it's a single document
+fn process_files(
+	store: &impl ForumStore,
+	links: &[String],
+	display: &DisplayArgs,
+) -> Result<(), CliError> {
+	let mut json_threads = Vec::new();
+	for link in links {
+		json_threads.extend(process_file(store, link, display)?);
+	}
+	if display.output_format() == OutputFormat::Json {
+		output_json(&json_threads);
+	}
+	Ok(())
+}
 
-	let output_for_machines = arguments.contains(&Argument::MachineOutput);
-	if output_for_machines {
-		output_for_machine(&messages)
-	} else {
-		output_for_human(&messages)
+/// Print the thread, or return it if it's being printed as JSON
+fn process_file(
+	store: &impl ForumStore,
+	link: &str,
+	display: &DisplayArgs,
+) -> Result<Option<JsonThread>, CliError> {
+	let format = display.output_format();
+	// JSON output has the link inside it so anything else would stop it being parsed
+	if matches!(format, OutputFormat::Human | OutputFormat::Machine) {
+		println!("File: {link}");
+	}
+	let thread = read_thread(store, link, display.mirrors)?;
+	let membership = Membership::from_header(&thread.header, link);
+	let mut mirrored_messages = thread.messages;
+	if let (NonMembers::Hide, Some(membership)) = (display.non_members, &membership) {
+		// Rejected messages are kept as they show the file has been tampered with
+		mirrored_messages.retain(|mirrored| match &mirrored.verified.message {
+			Some(message) => membership.status_of(message).is_member(),
+			None => true,
+		});
+	}
+	let verified_messages: Vec<VerifiedMessage> = mirrored_messages
+		.iter()
+		.map(|mirrored| mirrored.verified.clone())
+		.collect();
+	let messages: Vec<Message> = verified_messages
+		.iter()
+		.filter_map(|verified| verified.message.clone())
+		.collect();
+
+	let mut json_thread = None;
+	match format {
+		OutputFormat::Human => {
+			let options = HumanOptions {
+				expand: display.expand,
+				flag_members: membership
+					.as_ref()
+					.filter(|_| display.non_members == NonMembers::Flag),
+			};
+			output_for_human(&messages, &options);
+			output_rejected_for_human(&verified_messages);
+			if display.mirrors {
+				output_sources_for_human(&mirrored_messages)
+			}
+		}
+		OutputFormat::Machine => output_for_machine(&messages),
+		OutputFormat::Json => {
+			json_thread = Some(print_messages::json_thread(
+				link,
+				&mirrored_messages,
+				membership.as_ref(),
+			))
+		}
+		OutputFormat::Ndjson => output_ndjson(link, &mirrored_messages, membership.as_ref()),
 	}
 
-	if arguments.contains(&Argument::Interactive) {
-		interactive_session(link, messages);
+	if display.interactive {
+		interactive_session(store, link, messages)?;
 	}
+	Ok(json_thread)
 }
 
-fn get_messages(link: &str) -> Vec<Message> {
-	let error = match read::get_messages(link) {
-		Ok(res) => return res,
-		Err(e) => e,
-	};
-	match error {
-		Error::StdIo(e) => throw!("Async tokio error at {link}: {e}"),
-		Error::IPFS(e) => throw!("Error reading from IPFS at {link}: {e}"),
-		Error::FromUtf8(e) => throw!("Error parsing UTF-8 string from IPFS cat at {link}: {e}"),
-		Error::TomlDeserialization(e) => throw!("Error while parsing TOML at {link}: {e}"),
-		e => throw!("An unexpected error has occured when reading messages: {e}"),
+/// Read the thread, and its mirrors if asked to, warning about any mirrors that couldn't be read
+fn read_thread(
+	store: &impl ForumStore,
+	link: &str,
+	mirrors: bool,
+) -> Result<MirroredThread, CliError> {
+	if mirrors {
+		let thread =
+			blocking::get_mirrored_messages(store, link).map_err(|e| read_error(link, e))?;
+		for (mirror, error) in &thread.failed_mirrors {
+			eprintln!("Could not read mirror {mirror}: {}", full_message(error));
+		}
+		return Ok(thread);
 	}
+	let repo = blocking::get_repo(store, link).map_err(|e| read_error(link, e))?;
+	let messages = repo
+		.messages
+		.into_iter()
+		.map(|file_message| MirroredMessage {
+			verified: read::verify_message(file_message),
+			sources: vec![link.to_string()],
+		})
+		.collect();
+	Ok(MirroredThread {
+		header: repo.header,
+		messages,
+		failed_mirrors: Vec::new(),
+	})
 }
 
-fn interactive_session(messages_file: &str, messages: Vec<Message>) {
-	let keypair = account_manager::login("reference/accounts/");
-	let last_hash = match messages.last() {
-		Some(i) => i.get_hash(),
-		None => [0; 64],
+fn read_error(link: &str, error: Error) -> CliError {
+	let message = match error.root() {
+		Error::StdIo(e) => format!("Error reading {link}: {e}"),
+		Error::IPFS(e) => format!("Error reading from IPFS at {link}: {e}"),
+		Error::FromUtf8(e) => format!("Error parsing UTF-8 string from IPFS cat at {link}: {e}"),
+		Error::TomlDeserialization(e) => format!("Error while parsing TOML at {link}: {e}"),
+		Error::MsgpackDeserialization(e) => {
+			format!("Error while parsing the binary file at {link}: {e}")
+		}
+		Error::NotFound(e) => format!("Could not find {e} when reading {link}"),
+		Error::Timeout(_) => {
+			format!("Timed out reading from IPFS at {link}, is the daemon running?")
+		}
+		_ => format!(
+			"An unexpected error has occured when reading messages: {}",
+			full_message(&error)
+		),
 	};
-	write_cli::interactive_write(messages_file, keypair, last_hash);
+	CliError::new(ErrorKind::of(&error), message)
+}
+
+fn interactive_session(
+	store: &impl ForumStore,
+	messages_file: &str,
+	messages: Vec<Message>,
+) -> Result<(), CliError> {
+	let last_hash = chain_selection::select_last_hash(&messages);
+	let keypair = account_manager::login(ACCOUNTS_DIR)?;
+	write_cli::interactive_write(store, messages_file, keypair, last_hash)
 }
@@ -0,0 +1,91 @@
+use crypto_forum::{account_file::*, custom_types::Error, encrypt_decrypt::encrypt_and_write};
+use sha2::{Digest, Sha256};
+
+const TEST_DIR: &str = "test_data/";
+
+/// Cheap parameters so the tests run quickly
+const TEST_PARAMS: KdfParams = KdfParams {
+	memory_kib: 64,
+	iterations: 1,
+	parallelism: 1,
+};
+
+#[test]
+fn write_read() {
+	let test_path = &format!("{TEST_DIR}account_file_write_read");
+	std::fs::create_dir_all(TEST_DIR).unwrap();
+	let test_data = [7; 64];
+
+	write_account(test_path, &test_data, "password", &TEST_PARAMS).unwrap();
+	let account = read_account(test_path, "password").unwrap();
+	assert_eq!(account.data, test_data);
+	assert!(!account.needs_migration);
+
+	let wrong_password = read_account(test_path, "wrong");
+	let error = wrong_password.err().unwrap();
+	assert!(matches!(error.root(), Error::WrongPassword));
+	// Callers can tell which file it was without keeping track of it themselves
+	assert!(error.to_string().contains(test_path.as_str()));
+
+	std::fs::remove_file(test_path).unwrap();
+}
+
+#[test]
+fn legacy_file() {
+	let test_path = &format!("{TEST_DIR}account_file_legacy");
+	std::fs::create_dir_all(TEST_DIR).unwrap();
+	let test_data = [7; 64];
+	// Before account files had a header they were encrypted with the SHA-256 of the password
+	let legacy_key: [u8; 32] = Sha256::digest("password").into();
+	encrypt_and_write(test_path, &test_data, &legacy_key).unwrap();
+
+	let account = read_account(test_path, "password").unwrap();
+	assert_eq!(account.data, test_data);
+	assert!(account.needs_migration);
+
+	std::fs::remove_file(test_path).unwrap();
+}
+
+#[test]
+fn excessive_cost_rejected() {
+	let test_path = &format!("{TEST_DIR}account_file_excessive_cost");
+	std::fs::create_dir_all(TEST_DIR).unwrap();
+	write_account(test_path, &[7; 64], "password", &TEST_PARAMS).unwrap();
+
+	// Memory KiB comes straight after the magic and version byte, the checksum after the salt
+	let mut contents = std::fs::read(test_path).unwrap();
+	let memory_kib = KdfParams::MAX.memory_kib + 1;
+	contents[7..11].copy_from_slice(&memory_kib.to_le_bytes());
+	let checksum = Sha256::digest(&contents[..35]);
+	contents[35..39].copy_from_slice(&checksum[..4]);
+	std::fs::write(test_path, contents).unwrap();
+
+	let error = read_account(test_path, "password").err().unwrap();
+	assert!(matches!(error.root(), Error::InvalidFileData(_)));
+	assert!(error.root().to_string().contains("maximum"));
+
+	std::fs::remove_file(test_path).unwrap();
+}
+
+#[test]
+fn damaged_file() {
+	let test_path = &format!("{TEST_DIR}account_file_damaged");
+	std::fs::create_dir_all(TEST_DIR).unwrap();
+	write_account(test_path, &[7; 64], "password", &TEST_PARAMS).unwrap();
+	let contents = std::fs::read(test_path).unwrap();
+
+	// Damage to the salt or the encrypted data isn't mistaken for a wrong password
+	for index in [20, contents.len() - 1] {
+		let mut damaged = contents.clone();
+		damaged[index] ^= 1;
+		std::fs::write(test_path, damaged).unwrap();
+		let error = read_account(test_path, "password").err().unwrap();
+		assert!(matches!(error.root(), Error::InvalidFileData(_)));
+	}
+
+	std::fs::write(test_path, contents).unwrap();
+	let error = read_account(test_path, "wrong").err().unwrap();
+	assert!(matches!(error.root(), Error::WrongPassword));
+
+	std::fs::remove_file(test_path).unwrap();
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::get_keypair;
+use crypto_forum::{chains::*, custom_types::*};
+use ed25519_dalek::*;
+
+#[test]
+fn single_chain() {
+	let keypair = get_keypair(1);
+	let first = new_message(&keypair, ROOT_HASH, "first", true);
+	let second = new_message(&keypair, first.get_hash(), "second", true);
+	let messages = vec![first.clone(), second.clone()];
+
+	let chains = get_chains(&messages);
+	assert_eq!(chains, vec![vec![&first, &second]]);
+	assert!(get_forks(&messages).is_empty());
+}
+
+#[test]
+fn fork() {
+	let alice = get_keypair(1);
+	let bob = get_keypair(2);
+	let first = new_message(&alice, ROOT_HASH, "first", true);
+	let alice_reply = new_message(&alice, first.get_hash(), "from alice", true);
+	let bob_reply = new_message(&bob, first.get_hash(), "from bob", true);
+	let messages = vec![first.clone(), alice_reply.clone(), bob_reply.clone()];
+
+	let chains = get_chains(&messages);
+	assert_eq!(
+		chains,
+		vec![vec![&first, &alice_reply], vec![&first, &bob_reply]]
+	);
+	let forks = get_forks(&messages);
+	assert_eq!(
+		forks,
+		vec![Fork {
+			parent: first.get_hash(),
+			replies: vec![&alice_reply, &bob_reply],
+		}]
+	);
+}
+
+#[test]
+fn chain_stops_at_invalid_signature() {
+	let keypair = get_keypair(1);
+	let first = new_message(&keypair, ROOT_HASH, "first", true);
+	let forged = new_message(&keypair, first.get_hash(), "forged", false);
+	let after_forged = new_message(&keypair, forged.get_hash(), "after forged", true);
+	let messages = vec![first.clone(), forged, after_forged];
+
+	let chains = get_chains(&messages);
+	assert_eq!(chains, vec![vec![&first]]);
+}
+
+#[test]
+fn duplicates_are_not_forks() {
+	let keypair = get_keypair(1);
+	let first = new_message(&keypair, ROOT_HASH, "first", true);
+	let second = new_message(&keypair, first.get_hash(), "second", true);
+	let messages = vec![first.clone(), second.clone(), second.clone()];
+
+	assert_eq!(get_chains(&messages), vec![vec![&first, &second]]);
+	assert!(get_forks(&messages).is_empty());
+}
+
+#[test]
+fn orphan_starts_its_own_chain() {
+	let keypair = get_keypair(1);
+	let first = new_message(&keypair, ROOT_HASH, "first", true);
+	let orphan = new_message(&keypair, [1; 64], "parent is missing", true);
+	let messages = vec![first.clone(), orphan.clone()];
+
+	assert_eq!(get_chains(&messages), vec![vec![&first], vec![&orphan]]);
+}
+
+fn new_message(keypair: &Keypair, prev_hash: [u8; 64], body: &str, sign: bool) -> Message {
+	let to_sign = &[body.as_bytes(), &prev_hash].concat();
+	let signature = if sign {
+		keypair.sign(to_sign)
+	} else {
+		get_keypair(0).sign(to_sign)
+	};
+	Message {
+		prev_hash,
+		public_key: keypair.public,
+		body: body.to_string(),
+		signature,
+	}
+}
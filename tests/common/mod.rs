@@ -0,0 +1,30 @@
+//! Helpers shared by the integration tests, each test file only uses some of them
+#![allow(dead_code)]
+use base64::{engine::general_purpose, Engine};
+use crypto_forum::{
+	custom_types::ipns_link_to_key,
+	store::{ForumStore, MemoryStore},
+};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, SECRET_KEY_LENGTH};
+
+/// The same keypair every time for the same seed, so tests don't depend on randomness
+pub fn get_keypair(seed: u8) -> Keypair {
+	let secret = SecretKey::from_bytes(&[seed; SECRET_KEY_LENGTH]).unwrap();
+	let public = PublicKey::from(&secret);
+	Keypair { secret, public }
+}
+
+/// The public key as it's written in files
+pub fn key_of(keypair: &Keypair) -> String {
+	general_purpose::STANDARD_NO_PAD.encode(keypair.public.as_bytes())
+}
+
+/// Point the link at the value as TOML, without any of the checks writing it normally makes
+pub async fn publish(store: &MemoryStore, link: &str, value: &impl serde::Serialize) {
+	let contents = toml::to_string(value).unwrap().into_bytes();
+	let ipfs_link = store.add(contents).await.unwrap();
+	store
+		.publish(&ipns_link_to_key(link), &ipfs_link)
+		.await
+		.unwrap();
+}
@@ -0,0 +1,54 @@
+use crypto_forum::{
+	custom_types::*,
+	encoding::{self, FileFormat},
+	store::MemoryStore,
+	*,
+};
+
+#[test]
+fn convert_between_formats() {
+	let reference = std::fs::read("reference/reference.toml").unwrap();
+	let mut repo = encoding::decode(&reference).unwrap();
+	// Messages that aren't valid base64 must survive being converted
+	repo.messages[0].signature = "not base64!".to_string();
+	let toml = encoding::encode(&repo, FileFormat::Toml).unwrap();
+
+	let binary = encoding::convert(&toml, FileFormat::Binary).unwrap();
+	assert_eq!(encoding::detect_format(&binary), FileFormat::Binary);
+	assert!(binary.len() < toml.len());
+	assert_eq!(encoding::decode(&binary).unwrap(), repo);
+
+	let back = encoding::convert(&binary, FileFormat::Toml).unwrap();
+	assert_eq!(encoding::detect_format(&back), FileFormat::Toml);
+	assert_eq!(back, toml);
+}
+
+#[test]
+fn unsupported_version() {
+	let mut binary = encoding::encode(&FullFile::new(), FileFormat::Binary).unwrap();
+	// The version comes straight after the magic bytes
+	binary[b"CFBIN".len()] = encoding::BINARY_VERSION + 1;
+	let error = encoding::decode(&binary).unwrap_err();
+	assert!(matches!(error, Error::InvalidFileData(_)));
+}
+
+#[tokio::test]
+async fn writes_keep_the_format() {
+	let store = MemoryStore::new();
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	write::convert_repo(&store, link, FileFormat::Binary)
+		.await
+		.unwrap();
+
+	let reference = std::fs::read("reference/reference.toml").unwrap();
+	let file_message = encoding::decode(&reference).unwrap().messages[0].clone();
+	let message = read::verify_message(file_message).message.unwrap();
+	write::write_messages(&store, link, vec![message.clone()])
+		.await
+		.unwrap();
+
+	let contents = read::read_raw_file(&store, link).await.unwrap();
+	assert_eq!(encoding::detect_format(&contents), FileFormat::Binary);
+	let messages = read::get_messages(&store, link).await.unwrap();
+	assert_eq!(messages, vec![message]);
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use common::{get_keypair, key_of, publish};
+use crypto_forum::{custom_types::*, forum, store::MemoryStore, *};
+
+#[test]
+fn list_and_find_threads() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let manifest = ForumManifest::new("Forum".to_string());
+	let forum_link = &blocking::new_forum(&store, &manifest, &owner).unwrap();
+	let second = &blocking::new_ipns(&store, header("Second", 2)).unwrap();
+	let first = &blocking::new_ipns(&store, header("First", 1)).unwrap();
+	blocking::add_thread(&store, forum_link, second, &owner).unwrap();
+	blocking::add_thread(&store, forum_link, first, &owner).unwrap();
+	blocking::add_thread(&store, forum_link, "missing", &owner).unwrap();
+	// Adding a thread twice doesn't list it twice
+	blocking::add_thread(&store, forum_link, first, &owner).unwrap();
+
+	let manifest = blocking::get_manifest(&store, forum_link, Some(&key_of(&owner))).unwrap();
+	let expected = vec![second.clone(), first.clone(), "missing".to_string()];
+	assert_eq!(manifest.threads, expected);
+
+	let threads = blocking::list_threads(&store, forum_link, None).unwrap();
+	let links: Vec<&str> = threads.iter().map(|thread| thread.link.as_str()).collect();
+	assert_eq!(links, vec![first.as_str(), second.as_str(), "missing"]);
+	assert!(threads[2].header.is_err());
+
+	let found = forum::find_thread(&threads, 2).unwrap();
+	assert_eq!(&found.link, second);
+	assert_eq!(found.header.as_ref().unwrap().name, "Second");
+	assert!(forum::find_thread(&threads, 3).is_none());
+}
+
+#[tokio::test]
+async fn signed_manifest() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let manifest = ForumManifest::new("Forum".to_string());
+	let link = &forum::new_forum(&store, &manifest, &owner).await.unwrap();
+	let mut manifest = forum::get_manifest(&store, link, None).await.unwrap();
+
+	manifest.moderators.push(key_of(&get_keypair(2)));
+	manifest.users.push(ProfileLink {
+		public_key: key_of(&get_keypair(3)),
+		link: "profile".to_string(),
+	});
+	manifest.sign(link, &owner);
+	forum::write_manifest(&store, link, &manifest)
+		.await
+		.unwrap();
+	assert_eq!(
+		forum::get_manifest(&store, link, None).await.unwrap(),
+		manifest
+	);
+
+	// Only the owner can sign the next version
+	let mut takeover = manifest.clone();
+	takeover.name = "Mine now".to_string();
+	takeover.sign(link, &get_keypair(2));
+	let result = forum::write_manifest(&store, link, &takeover).await;
+	assert!(matches!(result.unwrap_err().root(), Error::Unauthorized(_)));
+
+	// Changing the manifest without signing it stops it being read
+	let mut tampered = manifest.clone();
+	tampered.moderators.clear();
+	publish(&store, link, &tampered).await;
+	let error = forum::get_manifest(&store, link, None).await.unwrap_err();
+	assert!(matches!(error.root(), Error::InvalidFileData(_)));
+}
+
+#[tokio::test]
+async fn expected_owner() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let impostor = get_keypair(2);
+	let manifest = ForumManifest::new("Forum".to_string());
+	let link = &forum::new_forum(&store, &manifest, &owner).await.unwrap();
+	let owner_key = &key_of(&owner);
+	forum::get_manifest(&store, link, Some(owner_key))
+		.await
+		.unwrap();
+
+	// Someone with the link's key can publish a manifest they signed themselves, which is only
+	// caught by readers that know who the owner should be
+	let mut replaced = manifest.clone();
+	replaced.sign(link, &impostor);
+	publish(&store, link, &replaced).await;
+	forum::get_manifest(&store, link, None).await.unwrap();
+	let error = forum::get_manifest(&store, link, Some(owner_key))
+		.await
+		.unwrap_err();
+	assert!(matches!(error.root(), Error::Unauthorized(_)));
+}
+
+#[tokio::test]
+async fn mirrored_manifest() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let owner_key = &key_of(&owner);
+	let manifest = ForumManifest::new("Forum".to_string());
+	let forum_link = &forum::new_forum(&store, &manifest, &owner).await.unwrap();
+	let original = forum::get_manifest(&store, forum_link, None).await.unwrap();
+
+	// An exact copy of the forum's manifest can be read from a mirror
+	let mirror = &forum::new_forum(&store, &manifest, &owner).await.unwrap();
+	publish(&store, mirror, &original).await;
+	let copy = forum::get_mirrored_manifest(&store, forum_link, mirror, owner_key)
+		.await
+		.unwrap();
+	assert_eq!(copy, original);
+
+	// The mirror's own manifest is signed for the mirror's link, not the forum's
+	let other = &forum::new_forum(&store, &manifest, &owner).await.unwrap();
+	let result = forum::get_mirrored_manifest(&store, forum_link, other, owner_key).await;
+	assert!(matches!(
+		result.unwrap_err().root(),
+		Error::InvalidFileData(_)
+	));
+
+	// A copy signed by whoever runs the mirror isn't the forum's
+	let mut forged = original.clone();
+	forged.name = "Mirror".to_string();
+	forged.sign(forum_link, &get_keypair(2));
+	publish(&store, mirror, &forged).await;
+	let result = forum::get_mirrored_manifest(&store, forum_link, mirror, owner_key).await;
+	assert!(matches!(result.unwrap_err().root(), Error::Unauthorized(_)));
+}
+
+#[test]
+fn reference_manifest() {
+	let contents = std::fs::read_to_string("reference/forum.toml").unwrap();
+	let manifest: ForumManifest = toml::from_str(&contents).unwrap();
+	let link = "a55d4454d1dae12a0c8a6f0a9286160d6376bcfb1273aa4d551d21f6aa523f56";
+	assert!(manifest.is_signed(link));
+	assert!(!manifest.is_signed(&manifest.mirrors[0]));
+	assert_eq!(
+		manifest.owner,
+		"KJcfesnQWKeDRKeuieJ49kwvoHBTSfrDW8Ao3P8M/k0"
+	);
+}
+
+fn header(name: &str, thread_number: u32) -> Header {
+	Header {
+		name: name.to_string(),
+		thread_number,
+		..Header::new()
+	}
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::{get_keypair, key_of, publish};
+use crypto_forum::{custom_types::*, store::MemoryStore, *};
+
+#[test]
+fn signed_revisions() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let link = &blocking::new_ipns(&store, Header::new()).unwrap();
+
+	let mut header = blocking::get_repo(&store, link).unwrap().header;
+	header.name = "General".to_string();
+	let first = HeaderRevision::new_signed(&header, link, &owner);
+	blocking::edit_header(&store, link, first).unwrap();
+	let mut header = blocking::get_repo(&store, link).unwrap().header;
+	header.tags = vec!["rust".to_string()];
+	let second = HeaderRevision::new_signed(&header, link, &owner);
+	blocking::edit_header(&store, link, second).unwrap();
+
+	// Only the owner can add revisions once there is one
+	let mut takeover = blocking::get_repo(&store, link).unwrap().header;
+	takeover.name = "Mine now".to_string();
+	let takeover = HeaderRevision::new_signed(&takeover, link, &get_keypair(2));
+	let result = blocking::edit_header(&store, link, takeover);
+	assert!(matches!(result.unwrap_err().root(), Error::Unauthorized(_)));
+
+	let header = blocking::get_repo(&store, link).unwrap().header;
+	assert_eq!(header.name, "General");
+	assert_eq!(header.tags, vec!["rust".to_string()]);
+	assert_eq!(header.revisions.len(), 2);
+	assert_eq!(header.owner, key_of(&owner));
+}
+
+#[test]
+fn created_with_header() {
+	let store = MemoryStore::new();
+	let header = Header {
+		name: "General".to_string(),
+		thread_number: 3,
+		tags: vec!["rust".to_string(), "ipfs".to_string()],
+		..Header::new()
+	};
+	let link = &blocking::new_ipns(&store, header.clone()).unwrap();
+
+	let repo = blocking::get_repo(&store, link).unwrap();
+	assert_eq!(repo.header, header);
+	assert!(repo.messages.is_empty());
+}
+
+#[test]
+fn created_with_owner() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let header = Header {
+		name: "General".to_string(),
+		..Header::new()
+	};
+	let link = &blocking::new_owned_ipns(&store, header, &owner).unwrap();
+
+	let header = blocking::get_repo(&store, link).unwrap().header;
+	assert_eq!(header.name, "General");
+	assert_eq!(header.owner, key_of(&owner));
+	assert_eq!(header.revisions.len(), 1);
+
+	// Nobody else can take it over by signing the next revision
+	let takeover = HeaderRevision::new_signed(&header, link, &get_keypair(2));
+	let result = blocking::edit_header(&store, link, takeover);
+	assert!(matches!(result.unwrap_err().root(), Error::Unauthorized(_)));
+}
+
+#[tokio::test]
+async fn tampered_header() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let mut header = read::get_repo(&store, link).await.unwrap().header;
+	header.name = "General".to_string();
+	let revision = HeaderRevision::new_signed(&header, link, &owner);
+	write::edit_header(&store, link, revision).await.unwrap();
+
+	// Renaming without signing is undone when reading
+	let mut repo = read::get_repo(&store, link).await.unwrap();
+	repo.header.name = "Renamed".to_string();
+	publish(&store, link, &repo).await;
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	assert_eq!(header.name, "General");
+
+	// Replacing the owner doesn't match who signed the first revision
+	let mut swapped = repo.clone();
+	swapped.header.owner = key_of(&get_keypair(2));
+	publish(&store, link, &swapped).await;
+	let error = read::get_repo(&store, link).await.unwrap_err();
+	assert!(matches!(error.root(), Error::InvalidFileData(_)));
+}
+
+#[tokio::test]
+async fn dropped_revisions() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let mut header = read::get_repo(&store, link).await.unwrap().header;
+	header.name = "General".to_string();
+	let revision = HeaderRevision::new_signed(&header, link, &owner);
+	write::edit_header(&store, link, revision).await.unwrap();
+
+	// Clearing the revisions would leave the unsigned name, so it's refused while there's an owner
+	let mut repo = read::get_repo(&store, link).await.unwrap();
+	repo.header.revisions.clear();
+	repo.header.name = "Renamed".to_string();
+	publish(&store, link, &repo).await;
+	let error = read::get_repo(&store, link).await.unwrap_err();
+	assert!(matches!(error.root(), Error::InvalidFileData(_)));
+}
+
+#[tokio::test]
+async fn swapped_owner() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let attacker = get_keypair(2);
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let mut header = read::get_repo(&store, link).await.unwrap().header;
+	header.name = "General".to_string();
+	let revision = HeaderRevision::new_signed(&header, link, &owner);
+	write::edit_header(&store, link, revision).await.unwrap();
+
+	// Revisions signed by anyone other than the first revision's signer are ignored
+	let mut repo = read::get_repo(&store, link).await.unwrap();
+	let mut renamed = repo.header.clone();
+	renamed.name = "Mine now".to_string();
+	let revision = HeaderRevision::new_signed(&renamed, link, &attacker);
+	repo.header.revisions.push(revision);
+	publish(&store, link, &repo).await;
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	assert_eq!(header.name, "General");
+	assert_eq!(header.owner, key_of(&owner));
+
+	// As is setting the owner to match them
+	repo.header.owner = key_of(&attacker);
+	publish(&store, link, &repo).await;
+	let error = read::get_repo(&store, link).await.unwrap_err();
+	assert!(matches!(error.root(), Error::InvalidFileData(_)));
+}
+
+#[tokio::test]
+async fn revisions_from_another_thread() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let other = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let mut header = read::get_repo(&store, other).await.unwrap().header;
+	header.name = "Other".to_string();
+	let revision = HeaderRevision::new_signed(&header, other, &owner);
+	write::edit_header(&store, other, revision.clone())
+		.await
+		.unwrap();
+
+	// The revision signs the link it was made for
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let result = write::edit_header(&store, link, revision.clone()).await;
+	assert!(matches!(result.unwrap_err().root(), Error::Unauthorized(_)));
+
+	let mut repo = read::get_repo(&store, link).await.unwrap();
+	repo.header.revisions.push(revision);
+	publish(&store, link, &repo).await;
+	let error = read::get_repo(&store, link).await.unwrap_err();
+	assert!(matches!(error.root(), Error::InvalidFileData(_)));
+}
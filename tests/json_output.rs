@@ -0,0 +1,65 @@
+mod common;
+
+use common::get_keypair;
+use crypto_forum::{chains::ROOT_HASH, custom_types::*, local_store::LocalStore, *};
+use serde_json::Value;
+use std::process::Command;
+
+const TEST_DIR: &str = "test_data/json_output";
+
+/// The field names are part of the schema, so changing them needs a new `schema_version`
+#[test]
+fn schema() {
+	let _ = std::fs::remove_dir_all(TEST_DIR);
+	let store = LocalStore::new(TEST_DIR).unwrap();
+	let keypair = get_keypair(1);
+	let first = &blocking::new_ipns(&store, Header::new()).unwrap();
+	let second = &blocking::new_ipns(&store, Header::new()).unwrap();
+	let message = Message::new_signed("Hello".to_string(), ROOT_HASH, &keypair);
+	blocking::write_messages(&store, first, vec![message]).unwrap();
+
+	let output = Command::new(env!("CARGO_BIN_EXE_crypto_forum"))
+		.args([
+			"--local", TEST_DIR, "read", "--format", "json", first, second,
+		])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+
+	// Several links are still one document
+	let threads: Vec<Value> = serde_json::from_slice(&output.stdout).unwrap();
+	assert_eq!(threads.len(), 2);
+	assert_eq!(keys(&threads[0]), ["link", "messages", "schema_version"]);
+	assert_eq!(threads[0]["schema_version"], 1);
+	assert_eq!(threads[0]["link"], first.as_str());
+	assert_eq!(
+		keys(&threads[0]["messages"][0]),
+		[
+			"body",
+			"field",
+			"hash",
+			"member",
+			"prev_hash",
+			"public_key",
+			"signed",
+			"sources",
+			"status",
+			"username",
+		]
+	);
+	assert_eq!(threads[0]["messages"][0]["status"], "valid");
+	assert_eq!(threads[1]["messages"], Value::Array(Vec::new()));
+
+	std::fs::remove_dir_all(TEST_DIR).unwrap();
+}
+
+fn keys(value: &Value) -> Vec<&str> {
+	let mut keys: Vec<&str> = value
+		.as_object()
+		.unwrap()
+		.keys()
+		.map(String::as_str)
+		.collect();
+	keys.sort();
+	keys
+}
@@ -0,0 +1,230 @@
+mod common;
+
+use common::{get_keypair, publish};
+use crypto_forum::{
+	chains::ROOT_HASH,
+	custom_types::*,
+	encoding::{self, FileFormat},
+	linked::{self, MessageCache},
+	store::{ForumStore, MemoryStore},
+	*,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[tokio::test]
+async fn link_existing_thread() {
+	let store = MemoryStore::new();
+	let keypair = get_keypair(1);
+	let first = Message::new_signed("first".to_string(), ROOT_HASH, &keypair);
+	let second = Message::new_signed("second".to_string(), first.get_hash(), &keypair);
+	let fork = Message::new_signed("fork".to_string(), first.get_hash(), &keypair);
+	let messages = vec![first, second, fork];
+
+	for format in [FileFormat::Toml, FileFormat::Binary] {
+		// Threads made by older versions have their messages in the file
+		let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+		publish(&store, link, &FullFile::new()).await;
+		write::convert_repo(&store, link, format).await.unwrap();
+		write::write_messages(&store, link, messages.clone())
+			.await
+			.unwrap();
+		write::link_messages(&store, link).await.unwrap();
+
+		let (stored, _) = read::get_repo_with_format(&store, link).await.unwrap();
+		assert!(stored.messages.is_empty());
+		assert_eq!(stored.heads.unwrap().len(), 2);
+		assert_eq!(read::get_messages(&store, link).await.unwrap(), messages);
+	}
+}
+
+#[tokio::test]
+async fn append_and_read_incrementally() {
+	let store = CountingStore::default();
+	let keypair = get_keypair(1);
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+
+	let mut messages = Vec::new();
+	let mut prev_hash = ROOT_HASH;
+	for body in ["one", "two", "three"] {
+		let message = Message::new_signed(body.to_string(), prev_hash, &keypair);
+		prev_hash = message.get_hash();
+		messages.push(message);
+	}
+	write::write_messages(&store, link, messages[..2].to_vec())
+		.await
+		.unwrap();
+	let mut cache = MessageCache::new();
+	read::get_repo_cached(&store, link, &mut cache)
+		.await
+		.unwrap();
+	assert_eq!(cache.len(), 2);
+
+	// Replying to the end of the chain only reads the thread file
+	let before = store.cats();
+	write::write_messages(&store, link, messages[2..].to_vec())
+		.await
+		.unwrap();
+	assert_eq!(store.cats() - before, 1);
+
+	// Reading again only fetches the thread file and the new message
+	let before = store.cats();
+	let repo = read::get_repo_cached(&store, link, &mut cache)
+		.await
+		.unwrap();
+	assert_eq!(store.cats() - before, 2);
+	let expected: Vec<FileMessage> = messages
+		.into_iter()
+		.map(write::message_to_file_message)
+		.collect();
+	assert_eq!(repo.messages, expected);
+}
+
+#[tokio::test]
+async fn chain_ends_from_heads() {
+	let store = CountingStore::default();
+	let keypair = get_keypair(1);
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let first = Message::new_signed("first".to_string(), ROOT_HASH, &keypair);
+	let second = Message::new_signed("second".to_string(), first.get_hash(), &keypair);
+	let fork = Message::new_signed("fork".to_string(), first.get_hash(), &keypair);
+	let ends = vec![second.get_hash(), fork.get_hash()];
+	write::write_messages(&store, link, vec![first, second, fork])
+		.await
+		.unwrap();
+
+	// Only the thread file and the two heads are fetched, not the message they both reply to
+	let before = store.cats();
+	let mut cache = MessageCache::new();
+	let actual = read::get_chain_ends(&store, link, &mut cache)
+		.await
+		.unwrap();
+	assert_eq!(store.cats() - before, 3);
+	assert_eq!(actual, ends);
+}
+
+#[tokio::test]
+async fn standalone_file() {
+	let store = MemoryStore::new();
+	let keypair = get_keypair(1);
+	let first = Message::new_signed("first".to_string(), ROOT_HASH, &keypair);
+	let second = Message::new_signed("second".to_string(), first.get_hash(), &keypair);
+	let messages = vec![first, second];
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	write::write_messages(&store, link, messages.clone())
+		.await
+		.unwrap();
+
+	let contents = read::read_standalone_file(&store, link).await.unwrap();
+	let repo = encoding::decode(&contents).unwrap();
+	assert!(repo.heads.is_none());
+	let expected: Vec<FileMessage> = messages
+		.into_iter()
+		.map(write::message_to_file_message)
+		.collect();
+	assert_eq!(repo.messages, expected);
+}
+
+#[tokio::test]
+async fn reply_to_missing_message() {
+	let store = MemoryStore::new();
+	let keypair = get_keypair(1);
+	let link = &write::new_ipns(&store, Header::new()).await.unwrap();
+	let first = Message::new_signed("first".to_string(), ROOT_HASH, &keypair);
+	write::write_messages(&store, link, vec![first.clone()])
+		.await
+		.unwrap();
+
+	// The reply can't be linked to a message the thread doesn't have, so nothing is written
+	let elsewhere = Message::new_signed("elsewhere".to_string(), ROOT_HASH, &keypair);
+	let reply = Message::new_signed("reply".to_string(), elsewhere.get_hash(), &keypair);
+	let result = write::write_messages(&store, link, vec![reply]).await;
+	assert!(matches!(
+		result.unwrap_err().root(),
+		Error::InvalidFileData(_)
+	));
+	assert_eq!(read::get_messages(&store, link).await.unwrap(), vec![first]);
+}
+
+#[tokio::test]
+async fn reply_to_undecodable_head() {
+	let store = MemoryStore::new();
+	let keypair = get_keypair(1);
+	let first = Message::new_signed("first".to_string(), ROOT_HASH, &keypair);
+	let mut undecodable = write::message_to_file_message(first);
+	undecodable.signature = "not base64!".to_string();
+	let mut heads = Vec::new();
+	let mut cache = MessageCache::new();
+	linked::append(
+		&store,
+		&mut heads,
+		vec![undecodable.clone()],
+		FileFormat::Toml,
+		&mut cache,
+	)
+	.await
+	.unwrap();
+	assert_eq!(heads[0].hash, None);
+
+	// A message with no hash can't be replied to, even by a reply with an empty hash
+	let reply = FileMessage {
+		prev_hash: String::new(),
+		..undecodable
+	};
+	let result = linked::append(
+		&store,
+		&mut heads,
+		vec![reply],
+		FileFormat::Toml,
+		&mut cache,
+	)
+	.await;
+	assert!(matches!(
+		result.unwrap_err().root(),
+		Error::InvalidFileData(_)
+	));
+	assert_eq!(heads.len(), 1);
+}
+
+/// Counts how many times content is fetched
+#[derive(Default)]
+struct CountingStore {
+	store: MemoryStore,
+	cats: AtomicUsize,
+}
+
+impl CountingStore {
+	fn cats(&self) -> usize {
+		self.cats.load(Ordering::SeqCst)
+	}
+}
+
+impl ForumStore for CountingStore {
+	async fn resolve(&self, name: &str) -> Result<String, Error> {
+		self.store.resolve(name).await
+	}
+
+	async fn cat(&self, path: &str) -> Result<Vec<u8>, Error> {
+		self.cats.fetch_add(1, Ordering::SeqCst);
+		self.store.cat(path).await
+	}
+
+	async fn add(&self, data: Vec<u8>) -> Result<String, Error> {
+		self.store.add(data).await
+	}
+
+	async fn publish(&self, key: &str, path: &str) -> Result<(), Error> {
+		self.store.publish(key, path).await
+	}
+
+	async fn key_gen(&self, key: &str) -> Result<String, Error> {
+		self.store.key_gen(key).await
+	}
+
+	async fn key_rename(&self, key: &str, new_key: &str) -> Result<(), Error> {
+		self.store.key_rename(key, new_key).await
+	}
+
+	async fn key_rm(&self, key: &str) -> Result<(), Error> {
+		self.store.key_rm(key).await
+	}
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::{get_keypair, key_of, publish};
+use crypto_forum::{chains::ROOT_HASH, custom_types::*, members::*, store::MemoryStore, *};
+use ed25519_dalek::Keypair;
+
+#[tokio::test]
+async fn member_lists() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let moderator = get_keypair(2);
+	let member = get_keypair(3);
+	let new_member = get_keypair(4);
+
+	let link = &new_owned_thread(&store, &owner).await;
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let first_list = MemberList::new_signed(
+		&header,
+		link,
+		vec![key_of(&member)],
+		vec![key_of(&moderator)],
+		&owner,
+	);
+	write::add_member_list(&store, link, first_list)
+		.await
+		.unwrap();
+	// A moderator from the previous list can make a new one
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let second_list = MemberList::new_signed(
+		&header,
+		link,
+		vec![key_of(&member), key_of(&new_member)],
+		vec![key_of(&moderator)],
+		&moderator,
+	);
+	write::add_member_list(&store, link, second_list)
+		.await
+		.unwrap();
+	// But a member who isn't a moderator can't
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let takeover = MemberList::new_signed(
+		&header,
+		link,
+		vec![],
+		vec![key_of(&new_member)],
+		&new_member,
+	);
+	let result = write::add_member_list(&store, link, takeover).await;
+	assert!(matches!(result.unwrap_err().root(), Error::Unauthorized(_)));
+
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	assert_eq!(header.owner, key_of(&owner));
+	let membership = Membership::from_header(&header, link).unwrap();
+	let statuses: Vec<MemberStatus> = [&owner, &moderator, &member, &new_member, &get_keypair(5)]
+		.into_iter()
+		.map(|keypair| Message::new_signed("hi".to_string(), ROOT_HASH, keypair))
+		.map(|message| membership.status_of(&message))
+		.collect();
+	let expected = vec![
+		MemberStatus::Owner,
+		MemberStatus::Moderator,
+		MemberStatus::Member,
+		MemberStatus::Member,
+		MemberStatus::Unknown,
+	];
+	assert_eq!(statuses, expected);
+}
+
+#[test]
+fn tampered_member_list() {
+	let link = "thread";
+	let owner = get_keypair(1);
+	let header = Header {
+		owner: key_of(&owner),
+		..Header::new()
+	};
+	let mut list =
+		MemberList::new_signed(&header, link, vec![key_of(&get_keypair(3))], vec![], &owner);
+	list.members.push(key_of(&get_keypair(4)));
+	assert!(!list.is_signed(link, 0));
+
+	let header = Header {
+		member_lists: vec![list],
+		..header
+	};
+	assert_eq!(Membership::from_header(&header, link), None);
+}
+
+#[tokio::test]
+async fn replayed_member_list() {
+	let store = MemoryStore::new();
+	let owner = get_keypair(1);
+	let member = get_keypair(3);
+	let link = &new_owned_thread(&store, &owner).await;
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let with_member = MemberList::new_signed(&header, link, vec![key_of(&member)], vec![], &owner);
+	write::add_member_list(&store, link, with_member.clone())
+		.await
+		.unwrap();
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let removed = MemberList::new_signed(&header, link, vec![], vec![], &owner);
+	write::add_member_list(&store, link, removed).await.unwrap();
+
+	// Appending the old list again doesn't undo the removal, as it was signed for another position
+	let mut repo = read::get_repo(&store, link).await.unwrap();
+	repo.header.member_lists.push(with_member);
+	publish(&store, link, &repo).await;
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let membership = Membership::from_header(&header, link).unwrap();
+	assert!(membership.members.is_empty());
+
+	// Nor does a list the owner signed for the same position in another thread
+	let mut repo = read::get_repo(&store, link).await.unwrap();
+	repo.header.member_lists.pop();
+	let other = &new_owned_thread(&store, &owner).await;
+	let from_other =
+		MemberList::new_signed(&repo.header, other, vec![key_of(&member)], vec![], &owner);
+	repo.header.member_lists.push(from_other);
+	publish(&store, link, &repo).await;
+	let header = read::get_repo(&store, link).await.unwrap().header;
+	let membership = Membership::from_header(&header, link).unwrap();
+	assert!(membership.members.is_empty());
+}
+
+/// A thread with a header revision signed by the owner, which is what makes them the owner
+async fn new_owned_thread(store: &MemoryStore, owner: &Keypair) -> String {
+	let link = write::new_ipns(store, Header::new()).await.unwrap();
+	let revision = HeaderRevision::new_signed(&Header::new(), &link, owner);
+	write::edit_header(store, &link, revision).await.unwrap();
+	link
+}
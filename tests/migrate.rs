@@ -0,0 +1,53 @@
+use crypto_forum::{
+	custom_types::*,
+	encoding, migrate,
+	store::{ForumStore, MemoryStore},
+	*,
+};
+
+#[test]
+fn upgrade_version_0() {
+	let reference = std::fs::read_to_string("reference/reference.toml").unwrap();
+	assert_eq!(migrate::stored_version(&reference).unwrap(), 0);
+
+	let repo = migrate::upgrade(&reference).unwrap();
+	assert_eq!(repo.format_version, migrate::FORMAT_VERSION);
+	assert_eq!(repo.header, Header::new());
+	assert_eq!(repo.messages.len(), 3);
+}
+
+#[test]
+fn newer_version() {
+	let mut repo = FullFile::new();
+	repo.format_version = migrate::FORMAT_VERSION + 1;
+	for format in [encoding::FileFormat::Toml, encoding::FileFormat::Binary] {
+		let contents = encoding::encode(&repo, format).unwrap();
+		let error = encoding::decode(&contents).unwrap_err();
+		assert!(matches!(error, Error::InvalidFileData(_)));
+	}
+}
+
+#[tokio::test]
+async fn migrate_repo() {
+	let store = MemoryStore::new();
+	let reference = std::fs::read("reference/reference.toml").unwrap();
+	let link = &store.key_gen("reference").await.unwrap();
+	let key = &ipns_link_to_key(link);
+	store.key_rename("reference", key).await.unwrap();
+	let ipfs_link = store.add(reference).await.unwrap();
+	store.publish(key, &ipfs_link).await.unwrap();
+	let messages = read::get_verified_messages(&store, link).await.unwrap();
+
+	assert_eq!(write::migrate_repo(&store, link).await.unwrap(), 0);
+	let contents = read::read_raw_file(&store, link).await.unwrap();
+	assert_eq!(
+		encoding::stored_version(&contents).unwrap(),
+		migrate::FORMAT_VERSION
+	);
+	let migrated = read::get_verified_messages(&store, link).await.unwrap();
+	assert_eq!(migrated, messages);
+
+	// Migrating again leaves it as it is
+	let version = write::migrate_repo(&store, link).await.unwrap();
+	assert_eq!(version, migrate::FORMAT_VERSION);
+}
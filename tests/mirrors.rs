@@ -0,0 +1,49 @@
+mod common;
+
+use common::get_keypair;
+use crypto_forum::{chains::ROOT_HASH, custom_types::*, store::MemoryStore, *};
+
+#[test]
+fn union_of_mirrors() {
+	let store = MemoryStore::new();
+	let keypair = get_keypair(7);
+	let first = Message::new_signed("first".to_string(), ROOT_HASH, &keypair);
+	let second = Message::new_signed("second".to_string(), first.get_hash(), &keypair);
+	let on_mirror = Message::new_signed("on mirror".to_string(), second.get_hash(), &keypair);
+
+	let link = &blocking::new_ipns(&store, Header::new()).unwrap();
+	blocking::write_messages(&store, link, vec![first.clone(), second.clone()]).unwrap();
+	let mirror = &blocking::new_ipns(&store, Header::new()).unwrap();
+	let mirror_messages = vec![first.clone(), second.clone(), on_mirror.clone()];
+	blocking::write_messages(&store, mirror, mirror_messages).unwrap();
+
+	blocking::add_mirror(&store, link, mirror, &keypair).unwrap();
+	blocking::add_mirror(&store, link, "offline", &keypair).unwrap();
+	// Adding the same mirror twice doesn't list it twice
+	blocking::add_mirror(&store, link, mirror, &keypair).unwrap();
+
+	let thread = blocking::get_mirrored_messages(&store, link).unwrap();
+	assert_eq!(
+		thread.header.mirrors,
+		vec![mirror.clone(), "offline".to_string()]
+	);
+	let actual: Vec<(Option<Message>, Vec<String>)> = thread
+		.messages
+		.into_iter()
+		.map(|mirrored| (mirrored.verified.message, mirrored.sources))
+		.collect();
+	let both = vec![link.clone(), mirror.clone()];
+	let expected = vec![
+		(Some(first), both.clone()),
+		(Some(second), both),
+		(Some(on_mirror), vec![mirror.clone()]),
+	];
+	assert_eq!(actual, expected);
+
+	let failed: Vec<&str> = thread
+		.failed_mirrors
+		.iter()
+		.map(|(mirror, _)| mirror.as_str())
+		.collect();
+	assert_eq!(failed, vec!["offline"]);
+}
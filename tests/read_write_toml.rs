@@ -1,33 +1,109 @@
-use crypto_forum::{custom_types::*, *};
+use crypto_forum::{
+	custom_types::*,
+	ipfs_store::IpfsStore,
+	local_store::LocalStore,
+	store::{ForumStore, MemoryStore},
+	*,
+};
 
 #[test]
 fn read() {
 	let expected = get_test_data();
+	let store = IpfsStore::new().unwrap();
 
 	let reference_hash = "/ipns/k51qzi5uqu5di70nif47ek3yg20ltitgcjmuafwwq0jbhndat32n40zqmeybq2";
-	let actual = read::get_messages(&reference_hash).unwrap();
+	let actual = blocking::get_messages(&store, reference_hash).unwrap();
+	assert_eq!(actual, expected);
+}
+
+#[tokio::test]
+async fn read_local() {
+	let expected = get_test_data();
+	let test_dir = TestDir::new("read_local");
+	let store = LocalStore::new(&test_dir.path).unwrap();
+
+	let reference_file = std::fs::read("reference/reference.toml").unwrap();
+	let link = store.key_gen("reference").await.unwrap();
+	let ipfs_link = store.add(reference_file).await.unwrap();
+	store.publish("reference", &ipfs_link).await.unwrap();
+	let actual = read::get_messages(&store, &link).await.unwrap();
 	assert_eq!(actual, expected);
 }
 
 #[test]
 fn read_write() {
+	let store = IpfsStore::new().unwrap();
+	test_read_write(&store);
+}
+
+#[test]
+fn read_write_memory() {
+	let store = MemoryStore::new();
+	test_read_write(&store);
+}
+
+#[test]
+fn read_write_local() {
+	let test_dir = TestDir::new("read_write_local");
+	let store = LocalStore::new(&test_dir.path).unwrap();
+	test_read_write(&store);
+}
+
+#[tokio::test]
+async fn read_write_async() {
+	let store = std::sync::Arc::new(MemoryStore::new());
 	let test_data = get_test_data();
 
-	let link = &write::new_ipns().unwrap();
-	let _cleanup = IPNSKeyCleanup { link };
-	write::write_messages(&link, test_data.clone()).unwrap();
-	let actual = read::get_messages(&link).unwrap();
+	// Spawning the task makes sure the futures can be used in multi-threaded services
+	let written = test_data.clone();
+	let actual = tokio::spawn(async move {
+		let link = &write::new_ipns(&*store, Header::new()).await.unwrap();
+		write::write_messages(&*store, link, written).await.unwrap();
+		read::get_messages(&*store, link).await.unwrap()
+	})
+	.await
+	.unwrap();
 	assert_eq!(actual, test_data);
 }
 
-struct IPNSKeyCleanup<'a> {
+fn test_read_write(store: &impl ForumStore) {
+	let test_data = get_test_data();
+
+	let link = &blocking::new_ipns(store, Header::new()).unwrap();
+	let _cleanup = IPNSKeyCleanup { store, link };
+	blocking::write_messages(store, link, test_data.clone()).unwrap();
+	let actual = blocking::get_messages(store, link).unwrap();
+	assert_eq!(actual, test_data);
+}
+
+struct IPNSKeyCleanup<'a, S: ForumStore> {
+	store: &'a S,
 	link: &'a str,
 }
 
-impl Drop for IPNSKeyCleanup<'_> {
+impl<S: ForumStore> Drop for IPNSKeyCleanup<'_, S> {
 	fn drop(&mut self) {
 		let key = &ipns_link_to_key(self.link);
-		write::delete_key(key).unwrap();
+		blocking::delete_key(self.store, key).unwrap();
+	}
+}
+
+/// Removes the local store directory when the test ends
+struct TestDir {
+	path: String,
+}
+
+impl TestDir {
+	fn new(name: &str) -> Self {
+		let path = format!("test_data/{name}/");
+		let _ = std::fs::remove_dir_all(&path);
+		Self { path }
+	}
+}
+
+impl Drop for TestDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.path);
 	}
 }
 
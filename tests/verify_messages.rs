@@ -0,0 +1,71 @@
+use crypto_forum::{audit::*, custom_types::*, migrate, read::verify_message};
+
+#[test]
+fn reference() {
+	let statuses: Vec<MessageStatus> = get_reference_messages()
+		.into_iter()
+		.map(|file_message| verify_message(file_message).status)
+		.collect();
+	let expected = vec![
+		MessageStatus::Valid,
+		MessageStatus::BadSignature,
+		MessageStatus::Valid,
+	];
+	assert_eq!(statuses, expected);
+}
+
+#[test]
+fn tampered() {
+	let original = get_reference_messages().remove(0);
+
+	let mut bad_base64 = original.clone();
+	bad_base64.public_key = "not base64!".to_string();
+	let verified = verify_message(bad_base64);
+	assert_eq!(
+		verified.status,
+		MessageStatus::BadBase64(MessageField::PublicKey)
+	);
+	assert_eq!(verified.message, None);
+
+	let mut wrong_length = original.clone();
+	wrong_length.prev_hash = "AAAA".to_string();
+	let verified = verify_message(wrong_length);
+	assert_eq!(
+		verified.status,
+		MessageStatus::WrongLength(MessageField::PrevHash)
+	);
+
+	let mut changed_body = original;
+	changed_body.body = "goodbye".to_string();
+	let verified = verify_message(changed_body.clone());
+	assert_eq!(verified.status, MessageStatus::BadSignature);
+	// The original is kept so it can be shown to the user
+	assert_eq!(verified.file_message, changed_body);
+}
+
+#[test]
+fn audit() {
+	let mut file_messages = get_reference_messages();
+	// Move Bob's reply before the message it's replying to
+	let bobs_reply = file_messages.remove(2);
+	file_messages.insert(0, bobs_reply);
+	let verified: Vec<VerifiedMessage> = file_messages.into_iter().map(verify_message).collect();
+
+	let report = audit_messages(&verified);
+	assert!(!report.is_intact());
+	let failures: Vec<(usize, MessageStatus, Option<LinkProblem>)> = report
+		.failures()
+		.map(|entry| (entry.index, entry.status, entry.link_problem))
+		.collect();
+	let expected = vec![
+		(0, MessageStatus::Valid, Some(LinkProblem::ParentAfter)),
+		(2, MessageStatus::BadSignature, None),
+	];
+	assert_eq!(failures, expected);
+}
+
+fn get_reference_messages() -> Vec<FileMessage> {
+	let reference_file = std::fs::read_to_string("reference/reference.toml").unwrap();
+	let full_file = migrate::upgrade(&reference_file).unwrap();
+	full_file.messages
+}